@@ -0,0 +1,181 @@
+use std::io;
+
+use libchess::uci;
+
+use crate::{
+    board_editor,
+    engine_info::{self, EngineInfo, EngineOutput},
+    game::EngineInitPhase,
+    variant::Variant,
+    visual_board as vb,
+};
+
+pub const MAX_LINES: usize = 5;
+pub const MAX_PV_ARROWS: usize = 3;
+
+/// an engine searching the position it's given for as long as it stays the same, it's polled
+/// every frame from the ui thread
+pub struct Analysis {
+    engine: uci::Engine,
+    init_phase: EngineInitPhase,
+    chess960: bool,
+    variant: Variant,
+    multipv: usize,                 // what the engine was last told to use
+    current_search: Option<String>, // the position and multipv of the running search
+    stopping: bool,                 // waiting for the "bestmove" that ends the last search
+    pub lines: Vec<EngineInfo>,     // indexed by multipv - 1
+}
+
+impl Analysis {
+    pub fn new(path: &str, chess960: bool, variant: Variant) -> io::Result<Self> {
+        Ok(Self {
+            engine: uci::Engine::new(path)?,
+            init_phase: EngineInitPhase::SendUci,
+            chess960,
+            variant,
+            multipv: 1,
+            current_search: None,
+            stopping: false,
+            lines: Vec::new(),
+        })
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.init_phase == EngineInitPhase::End
+    }
+
+    /// (re)starts the search whenever the position or the number of lines changes
+    pub fn update(&mut self, position_cmd: &str, multipv: usize) -> io::Result<()> {
+        match self.init_phase {
+            EngineInitPhase::SendUci => {
+                self.engine.send(uci::UCI)?;
+                self.init_phase.cycle();
+            }
+            EngineInitPhase::WaitUciOk => {
+                if self.engine.try_get(uci::UCI_OK).is_some() {
+                    self.init_phase.cycle();
+                }
+            }
+            EngineInitPhase::SendNewGame => {
+                if self.chess960 {
+                    self.engine.send("setoption name UCI_Chess960 value true")?;
+                }
+                if let Some(name) = self.variant.to_uci_fmt() {
+                    self.engine
+                        .send(&format!("setoption name UCI_Variant value {name}"))?;
+                }
+                self.engine.send(uci::NEW_GAME)?;
+                self.engine.send(uci::IS_READY)?;
+                self.init_phase.cycle();
+            }
+            EngineInitPhase::WaitIsReady => {
+                if self.engine.try_get(uci::READY_OK).is_some() {
+                    self.init_phase.cycle();
+                }
+            }
+            EngineInitPhase::End => self.search(position_cmd, multipv)?,
+        }
+
+        Ok(())
+    }
+
+    fn search(&mut self, position_cmd: &str, multipv: usize) -> io::Result<()> {
+        while let Some(line) = self.engine.try_get("") {
+            match engine_info::parse_line(&line) {
+                // anything still coming in from a search that was stopped is about another position
+                EngineOutput::Info(info) if !self.stopping && info.score.is_some() => {
+                    let idx = info.multipv.clamp(1, MAX_LINES) - 1;
+                    if self.lines.len() <= idx {
+                        self.lines.resize(idx + 1, info.clone());
+                    }
+                    self.lines[idx] = info;
+                }
+                EngineOutput::BestMove(_) => self.stopping = false,
+                _ => {}
+            }
+        }
+
+        if self.stopping {
+            return Ok(());
+        }
+
+        let search = format!("{position_cmd} multipv {multipv}");
+
+        match &self.current_search {
+            Some(current) if *current == search => {}
+            Some(_) => {
+                self.engine.send(uci::STOP)?;
+                self.current_search = None;
+                self.stopping = true;
+            }
+            None => {
+                if multipv != self.multipv {
+                    self.engine
+                        .send(&format!("setoption name MultiPV value {multipv}"))?;
+                    self.multipv = multipv;
+                }
+
+                self.engine.send(position_cmd)?;
+                self.engine.send("go infinite")?;
+
+                self.current_search = Some(search);
+                self.lines.clear();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Analysis {
+    fn drop(&mut self) {
+        let _ = self.engine.send(uci::STOP);
+        let _ = self.engine.send("quit");
+    }
+}
+
+fn uci_to_arrow(mov: &str) -> Option<vb::Arrow> {
+    let (ff, fr) = board_editor::parse_square(mov.get(0..2)?)?;
+    let (tf, tr) = board_editor::parse_square(mov.get(2..4)?)?;
+
+    Some((ff, fr, tf, tr))
+}
+
+/// the best move, the second line's first move, and the next `pv_arrows` moves of the best line
+/// fading out, in the order they should be drawn
+pub fn engine_arrows(lines: &[EngineInfo], pv_arrows: usize) -> Vec<(vb::Arrow, vb::ArrowStyle)> {
+    let mut arrows = Vec::new();
+
+    if let Some(best) = lines.first() {
+        for (idx, mov) in best.pv.iter().enumerate().skip(1).take(pv_arrows) {
+            if let Some(arrow) = uci_to_arrow(mov) {
+                let alpha = vb::BEST_MOVE_ARROW.color.a * 0.6 / idx as f32;
+                arrows.push((
+                    arrow,
+                    vb::ArrowStyle {
+                        color: vb::BEST_MOVE_ARROW.color.with_alpha(alpha),
+                        ..vb::BEST_MOVE_ARROW
+                    },
+                ));
+            }
+        }
+    }
+
+    if let Some(arrow) = lines
+        .get(1)
+        .and_then(|l| l.pv.first())
+        .and_then(|m| uci_to_arrow(m))
+    {
+        arrows.push((arrow, vb::SECOND_LINE_ARROW));
+    }
+
+    if let Some(arrow) = lines
+        .first()
+        .and_then(|l| l.pv.first())
+        .and_then(|m| uci_to_arrow(m))
+    {
+        arrows.push((arrow, vb::BEST_MOVE_ARROW));
+    }
+
+    arrows
+}
@@ -10,7 +10,11 @@ use macroquad::{
     ui::{self, hash, widgets},
 };
 
-use crate::{events, game, ui_skins, visual_board as vb};
+use crate::{
+    analysis, board_editor, chess960, database, endgames, eval_graph, events, fen, game, odds, pgn,
+    puzzles, repertoire, review, rules, search, strength, ui_skins, variant::Variant,
+    visual_board as vb,
+};
 use libchess::{self as lc, color as chess_color, pos};
 
 const HUMAN_PLAYER: usize = 0;
@@ -21,9 +25,10 @@ static GAME: LazyLock<Mutex<Option<game::Game>>> = LazyLock::new(|| Mutex::new(N
 #[derive(Clone)]
 pub struct MouseInputSquares {
     pub keys_down: HashSet<KeyCode>, // clearly doesn't belong, but...
-    pub up_left: Option<pos::Square>,
-    pub down_left: Option<pos::Square>,
-    pub down_right: Option<pos::Square>,
+    pub up_left: Option<rules::Square>,
+    pub down_left: Option<rules::Square>,
+    pub down_right: Option<rules::Square>,
+    pub held_drop: Option<usize>, // a kind of piece from a Crazyhouse pocket, not `down_left`
 }
 
 #[derive(Default, Clone)]
@@ -39,6 +44,9 @@ pub enum State {
     },
     Home,
     GameSelection,
+    BoardEditor {
+        for_search: bool, // a position or pattern to look for in the database, not to play from
+    },
     TryStartGame {
         timer: time::Duration,
         max_time: time::Duration,
@@ -49,18 +57,34 @@ pub enum State {
     InGame,
     GameFinish {
         message: &'static str,
+        result: game::GameResult,
     },
+    Analysis {
+        history: rules::History,
+        ply: usize, // the one shown first
+    },
+    Puzzles,
+    Repertoire,
+    Endgames,
+    Database,
 }
 
 pub struct App {
     pub state: State,
     // pub game: Mutex<Option<game::Game>>,
-    pub game_settings: game::Settings,
+    pub game_settings: game::Settings, // as chosen on the game selection screen
+    pub started_settings: game::Settings, // what the current game was started with, odds included
     pub engines_list: Vec<EnginePath>,
     pub vb: vb::VisualBoard,
     pub font: Font,
     pub ui_skin: ui::Skin,
-    pub lc_data: lc::InitData,
+    pub review: Option<review::Review>,
+    pub zb: lc::ZobristValues, // for hashing positions in the database search
+    // the game database's filter and search, kept while the board editor draws a position
+    pub search_setup: board_editor::SetupPosition,
+    pub search_kind: usize, // index into search::KINDS
+    pub search_material: String,
+    pub database_filter: database::Filter,
 }
 
 trait Clock {
@@ -90,13 +114,13 @@ impl App {
             r: 184.0 / 255.0,
             g: 135.0 / 255.0,
             b: 98.0 / 255.0,
-            a: 255.0 / 255.0,
+            a: 1.0,
         };
         let dark = Color {
             r: 237.0 / 255.0,
             g: 214.0 / 255.0,
             b: 176.0 / 255.0,
-            a: 255.0 / 255.0,
+            a: 1.0,
         };
 
         let mut engines_list = Vec::new();
@@ -142,21 +166,34 @@ impl App {
                 btime_s: "600".to_string(),
                 wincrement_ms: "0".to_string(),
                 bincrement_ms: "0".to_string(),
+                review_engine_path: None,
+                review_movetime_ms: "500".to_string(),
+                chess960: false,
+                variant: Variant::Standard,
+                white_engine_elo: String::new(),
+                black_engine_elo: String::new(),
+                goal: None,
             },
+            started_settings: game::Settings::default(),
             engines_list,
             vb: vb::VisualBoard::new(light, dark, false),
             font: load_ttf_font("assets/fonts/GoogleSansCode-Regular.ttf")
                 .await
                 .unwrap(),
             ui_skin: ui::root_ui().default_skin(),
-            lc_data: lc::init(),
+            review: None,
+            zb: lc::init().zb,
+            search_setup: board_editor::SetupPosition::empty(),
+            search_kind: 0,
+            search_material: String::new(),
+            database_filter: database::Filter::default(),
         };
 
         a.ui_skin = ui_skins::standard(&a.font);
 
         ui::root_ui().push_skin(&a.ui_skin);
 
-        a.vb.sync_pieces(&pos::Position::from_fen(pos::START_FEN, &a.lc_data.zb));
+        a.vb.sync_board(&rules::Board::start());
 
         a
     }
@@ -166,11 +203,17 @@ impl App {
             match self.state {
                 State::AssetLoading { .. } => self.load_assets().await,
                 State::Home => self.home().await,
-                State::GameSelection { .. } => self.game_selection().await,
+                State::GameSelection => self.game_selection().await,
+                State::BoardEditor { .. } => self.board_editor().await,
                 State::TryStartGame { .. } => self.try_start_game().await,
                 State::TryStartFailed { .. } => self.try_start_failed().await,
                 State::InGame => self.in_game().await,
                 State::GameFinish { .. } => self.game_finish().await,
+                State::Analysis { .. } => self.analysis().await,
+                State::Puzzles => self.puzzles().await,
+                State::Repertoire => self.repertoire().await,
+                State::Endgames => self.endgames().await,
+                State::Database => self.database().await,
             }
 
             next_frame().await;
@@ -201,6 +244,41 @@ impl App {
         )
     }
 
+    fn player_names(&self) -> (String, String) {
+        (
+            match &self.started_settings.white_engine_path {
+                Some(p) => p.name.clone(),
+                None => "White".to_string(),
+            },
+            match &self.started_settings.black_engine_path {
+                Some(p) => p.name.clone(),
+                None => "Black".to_string(),
+            },
+        )
+    }
+
+    /// the finished game, with the review's marks if there is one
+    fn game_pgn(&self, message: &str, result: game::GameResult) -> String {
+        let (white, black) = self.player_names();
+        let game = GAME.lock().unwrap();
+        let game = game.as_ref().unwrap();
+
+        pgn::write(
+            &pgn::Tags {
+                white: &white,
+                black: &black,
+                result,
+                termination: message,
+                starting_fen: &self.started_settings.position_fen,
+                chess960: self.started_settings.chess960,
+                variant: self.started_settings.variant,
+                reviews: self.review.as_ref().map(|r| r.reviews.as_slice()),
+                records: &game.move_records,
+            },
+            &game.history.sans(),
+        )
+    }
+
     fn ui_window_pos(&self) -> Vec2 {
         vec2(self.vb.pos.x + self.vb.board_width() + 10.0, self.vb.pos.y)
     }
@@ -212,7 +290,7 @@ impl App {
             unreachable!()
         };
 
-        self.vb.load_piece_assets(&piece_paths).await;
+        self.vb.load_piece_assets(piece_paths).await;
         self.vb.load_board_assets().await;
 
         self.state = State::Home;
@@ -223,13 +301,13 @@ impl App {
 
         loop {
             clear_background(DARKGRAY);
-            self.vb.draw_board(None, &self.lc_data);
+            self.vb.draw_board(None);
 
-            self.ui_window("Home").ui(&mut ui::root_ui(), |mut ui| {
+            self.ui_window("Home").ui(&mut ui::root_ui(), |ui| {
                 if widgets::Button::new("New Game")
                     .position(vec2(0.0, 0.0))
                     .size(vec2(self.ui_window_size().x / 2.0 - 2.5, 100.0))
-                    .ui(&mut ui)
+                    .ui(ui)
                 {
                     self.state = State::GameSelection;
                     should_break = true;
@@ -237,14 +315,69 @@ impl App {
                 widgets::Button::new("Multiplayer (eventually)")
                     .position(vec2(self.ui_window_size().x / 2.0 + 5.0, 0.0))
                     .size(vec2(self.ui_window_size().x / 2.0 - 2.5, 100.0))
-                    .ui(&mut ui);
+                    .ui(ui);
+
+                if widgets::Button::new("Analysis Board")
+                    .position(vec2(0.0, 105.0))
+                    .size(vec2(self.ui_window_size().x / 2.0 - 2.5, 40.0))
+                    .ui(ui)
+                {
+                    let board = rules::Board::from_fen(
+                        &self.game_settings.position_fen,
+                        self.game_settings.variant,
+                        self.game_settings.chess960,
+                    )
+                    .unwrap_or_else(|_| rules::Board::start());
+
+                    self.state = State::Analysis {
+                        history: rules::History::new(board),
+                        ply: 0,
+                    };
+                    should_break = true;
+                }
+
+                if widgets::Button::new("Puzzles")
+                    .position(vec2(self.ui_window_size().x / 2.0 + 5.0, 105.0))
+                    .size(vec2(self.ui_window_size().x / 2.0 - 2.5, 40.0))
+                    .ui(ui)
+                {
+                    self.state = State::Puzzles;
+                    should_break = true;
+                }
+
+                if widgets::Button::new("Opening Trainer")
+                    .position(vec2(0.0, 150.0))
+                    .size(vec2(self.ui_window_size().x / 2.0 - 2.5, 40.0))
+                    .ui(ui)
+                {
+                    self.state = State::Repertoire;
+                    should_break = true;
+                }
+
+                if widgets::Button::new("Endgame Drills")
+                    .position(vec2(self.ui_window_size().x / 2.0 + 5.0, 150.0))
+                    .size(vec2(self.ui_window_size().x / 2.0 - 2.5, 40.0))
+                    .ui(ui)
+                {
+                    self.state = State::Endgames;
+                    should_break = true;
+                }
+
+                if widgets::Button::new("Game Database")
+                    .position(vec2(0.0, 195.0))
+                    .size(vec2(self.ui_window_size().x + 2.5, 40.0))
+                    .ui(ui)
+                {
+                    self.state = State::Database;
+                    should_break = true;
+                }
 
                 let tmp = fs::read_dir("assets/pieces/")
                     .unwrap()
                     .map(|e| e.unwrap().file_name().into_string().unwrap())
                     .collect::<Vec<String>>();
 
-                for _ in 0..45 {
+                for _ in 0..75 {
                     ui.separator();
                 }
 
@@ -268,6 +401,16 @@ impl App {
                     &tmp.iter().map(|t| t.as_str()).collect::<Vec<&str>>(),
                     None,
                 );
+
+                self.vb.piece_display = vb::PieceDisplay::ALL[ui.combo_box(
+                    hash!(),
+                    "Piece Display",
+                    &vb::PieceDisplay::ALL
+                        .iter()
+                        .map(|d| d.to_display_fmt())
+                        .collect::<Vec<&str>>(),
+                    None,
+                )];
             });
 
             if should_break {
@@ -292,13 +435,32 @@ impl App {
 
         loop {
             clear_background(DARKGRAY);
-            self.vb.draw_board(None, &self.lc_data);
+            self.vb.draw_board(None);
 
             self.ui_window("Game Selection")
                 .ui(&mut ui::root_ui(), |ui| {
                     let white_player = ui.combo_box(hash!(), "White Engine", &list, None);
                     let black_player = ui.combo_box(hash!(), "Black Engine", &list, None);
 
+                    ui.input_text(
+                        hash!(),
+                        "White Engine Elo",
+                        &mut self.game_settings.white_engine_elo,
+                    );
+                    ui.input_text(
+                        hash!(),
+                        "Black Engine Elo",
+                        &mut self.game_settings.black_engine_elo,
+                    );
+                    ui.label(
+                        None,
+                        &format!(
+                            "(Leave empty for full strength, {} to {})",
+                            strength::WEAKEST_ELO,
+                            strength::STRONGEST_ELO
+                        ),
+                    );
+
                     for _ in 0..10 {
                         // no idea how to make the separator larger
                         ui.separator();
@@ -339,19 +501,83 @@ impl App {
                         &mut self.game_settings.bincrement_ms,
                     );
 
-                    ui.input_text(hash!(), "Max Engine Thinking Time", &mut self.game_settings.max_engine_think_time_s);
+                    ui.input_text(
+                        hash!(),
+                        "Max Engine Thinking Time",
+                        &mut self.game_settings.max_engine_think_time_s,
+                    );
                     ui.label(None, "(Leave empty or 0 for unlimited time)");
 
                     for _ in 0..10 {
                         ui.separator();
                     }
 
-                    ui.input_text(hash!(), "FEN", &mut self.game_settings.position_fen);
+                    let review_engine = ui.combo_box(hash!(), "Review Engine", &list, None);
+                    ui.input_text(
+                        hash!(),
+                        "Review Time Per Move (milliseconds)",
+                        &mut self.game_settings.review_movetime_ms,
+                    );
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
                     ui.label(
                         None,
-                        "(!) An incorrect fen string will crash the application and I am too lazy to fix it",
+                        &format!("Position: {}", self.game_settings.position_fen),
                     );
 
+                    if let Err(e) = fen::normalize(
+                        &self.game_settings.position_fen,
+                        self.game_settings.variant,
+                        self.game_settings.chess960,
+                    ) {
+                        ui.label(None, &format!("(!) {e}"));
+                    }
+
+                    if ui.button(None, "Set Up Position") {
+                        self.state = State::BoardEditor { for_search: false };
+                        should_break = true;
+                    }
+
+                    ui.checkbox(hash!(), "Chess960", &mut self.game_settings.chess960);
+
+                    if self.game_settings.chess960 && ui.button(None, "Random Chess960 Position") {
+                        self.game_settings.position_fen = chess960::random_start_fen();
+                    }
+
+                    let last_variant = self.game_settings.variant;
+                    self.game_settings.variant = Variant::ALL[ui.combo_box(
+                        hash!(),
+                        "Variant",
+                        &Variant::ALL
+                            .iter()
+                            .map(|v| v.to_display_fmt())
+                            .collect::<Vec<&str>>(),
+                        None,
+                    )];
+
+                    // Horde doesn't start from the usual position, an untouched one follows along
+                    if self.game_settings.variant != last_variant
+                        && self.game_settings.position_fen.trim() == last_variant.start_fen()
+                    {
+                        self.game_settings.position_fen =
+                            self.game_settings.variant.start_fen().to_string();
+                    }
+
+                    let odds = odds::Odds::ALL[ui.combo_box(
+                        hash!(),
+                        "Odds",
+                        &odds::Odds::ALL
+                            .iter()
+                            .map(|o| o.to_display_fmt())
+                            .collect::<Vec<&str>>(),
+                        None,
+                    )];
+                    let odds_giver =
+                        ui.combo_box(hash!(), "Odds Given By", &["White", "Black"], None);
+
                     if self.game_settings.wincrement_ms.len() > 7 {
                         self.game_settings.wincrement_ms.truncate(7);
                     }
@@ -360,17 +586,29 @@ impl App {
                         self.game_settings.bincrement_ms.truncate(7);
                     }
 
-
                     if self.game_settings.max_engine_think_time_s.len() > 7 {
                         self.game_settings.max_engine_think_time_s.truncate(7);
                     }
 
-                    if self.game_settings.max_engine_think_time_s == "" {
+                    if self.game_settings.review_movetime_ms.len() > 7 {
+                        self.game_settings.review_movetime_ms.truncate(7);
+                    }
+
+                    if self.game_settings.white_engine_elo.len() > 4 {
+                        self.game_settings.white_engine_elo.truncate(4);
+                    }
+
+                    if self.game_settings.black_engine_elo.len() > 4 {
+                        self.game_settings.black_engine_elo.truncate(4);
+                    }
+
+                    if self.game_settings.max_engine_think_time_s.is_empty() {
                         self.game_settings.max_engine_think_time_s = "0".to_string();
                     }
 
-                    if self.game_settings.position_fen.replace(" ", "") == "".to_string() {
-                        self.game_settings.position_fen = pos::START_FEN.to_string();
+                    if self.game_settings.position_fen.replace(' ', "").is_empty() {
+                        self.game_settings.position_fen =
+                            self.game_settings.variant.start_fen().to_string();
                     }
 
                     for _ in 0..10 {
@@ -378,15 +616,24 @@ impl App {
                     }
 
                     if ui.button(None, "Play")
-                        && self.game_settings.wtime_s != ""
-                        && self.game_settings.btime_s != ""
-                        && self.game_settings.wincrement_ms != ""
-                        && self.game_settings.bincrement_ms != ""
+                        && !self.game_settings.wtime_s.is_empty()
+                        && !self.game_settings.btime_s.is_empty()
+                        && !self.game_settings.wincrement_ms.is_empty()
+                        && !self.game_settings.bincrement_ms.is_empty()
                         && self.game_settings.wtime_s.parse::<u64>().is_ok()
                         && self.game_settings.btime_s.parse::<u64>().is_ok()
                         && self.game_settings.wincrement_ms.parse::<u64>().is_ok()
                         && self.game_settings.bincrement_ms.parse::<u64>().is_ok()
-                        && self.game_settings.max_engine_think_time_s.parse::<u64>().is_ok()
+                        && self
+                            .game_settings
+                            .max_engine_think_time_s
+                            .parse::<u64>()
+                            .is_ok()
+                        && self.game_settings.review_movetime_ms.parse::<u64>().is_ok()
+                        && (self.game_settings.white_engine_elo.is_empty()
+                            || self.game_settings.white_engine_elo.parse::<u32>().is_ok())
+                        && (self.game_settings.black_engine_elo.is_empty()
+                            || self.game_settings.black_engine_elo.parse::<u32>().is_ok())
                     {
                         self.game_settings.white_engine_path = if white_player == HUMAN_PLAYER {
                             None
@@ -400,6 +647,16 @@ impl App {
                             Some(self.engines_list[black_player - 1].clone())
                         };
 
+                        self.game_settings.review_engine_path = if review_engine == 0 {
+                            None
+                        } else {
+                            Some(self.engines_list[review_engine - 1].clone())
+                        };
+
+                        // the odds only apply to this game, the choices stay as they were
+                        self.started_settings = self.game_settings.clone();
+                        odds.apply(&mut self.started_settings, odds_giver == 0);
+
                         self.state = State::TryStartGame {
                             timer: time::Duration::ZERO,
                             max_time: time::Duration::from_secs(15),
@@ -424,17 +681,183 @@ impl App {
         }
     }
 
-    async fn try_start_game(&mut self) {
-        let mut game = GAME.lock().unwrap();
+    async fn board_editor(&mut self) {
+        let for_search = matches!(self.state, State::BoardEditor { for_search: true });
+        let (mut editor, back) = if for_search {
+            (
+                board_editor::Editor::new(&self.search_setup.to_fen()),
+                State::Database,
+            )
+        } else {
+            (
+                board_editor::Editor::new(&self.game_settings.position_fen),
+                State::GameSelection,
+            )
+        };
+
+        let mut should_break = false;
+
+        loop {
+            clear_background(DARKGRAY);
+            self.vb.draw_setup(&editor.setup);
+
+            // white pieces on the first row, black on the second, in texture order
+            let palette_square_size = (self.ui_window_size().x / 6.0).min(self.vb.square_size);
+            let palette = (0..12)
+                .map(|piece| {
+                    (
+                        piece,
+                        Rect::new(
+                            self.ui_window_pos().x + (piece % 6) as f32 * palette_square_size,
+                            self.ui_window_pos().y + (piece / 6) as f32 * palette_square_size,
+                            palette_square_size,
+                            palette_square_size,
+                        ),
+                    )
+                })
+                .collect::<Vec<(usize, Rect)>>();
+
+            for (piece, rect) in &palette {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, GRAY);
+                self.vb
+                    .draw_piece_texture(*piece, rect.point(), palette_square_size);
+            }
+
+            events::do_editor_mouse_events(&self.vb, &mut editor, &palette);
+
+            let controls_y = palette_square_size * 2.0 + 10.0;
+
+            widgets::Window::new(
+                hash!(),
+                self.ui_window_pos() + vec2(0.0, controls_y),
+                self.ui_window_size() - vec2(0.0, controls_y),
+            )
+            .movable(false)
+            .titlebar(true)
+            .label("Board Editor")
+            .ui(&mut ui::root_ui(), |ui| {
+                ui.combo_box(
+                    hash!(),
+                    "Side To Move",
+                    &["White", "Black"],
+                    &mut editor.side_to_move,
+                );
+
+                ui.checkbox(hash!(), "White O-O", &mut editor.setup.castling[0]);
+                ui.same_line(120.0);
+                ui.checkbox(hash!(), "White O-O-O", &mut editor.setup.castling[1]);
+                ui.checkbox(hash!(), "Black O-O", &mut editor.setup.castling[2]);
+                ui.same_line(120.0);
+                ui.checkbox(hash!(), "Black O-O-O", &mut editor.setup.castling[3]);
+
+                ui.input_text(hash!(), "En Passant Square", &mut editor.en_passant_text);
+                ui.input_text(hash!(), "Halfmove Clock", &mut editor.halfmoves_text);
+                ui.input_text(hash!(), "Fullmove Number", &mut editor.fullmoves_text);
+
+                for _ in 0..5 {
+                    ui.separator();
+                }
+
+                ui.input_text(hash!(), "FEN", &mut editor.fen_text);
+
+                if ui.button(None, "Load FEN") {
+                    let fen = editor.fen_text.clone();
+                    editor.load(&fen);
+                }
+
+                ui.same_line(100.0);
+
+                if ui.button(None, "Start Position") {
+                    editor.load(self.game_settings.variant.start_fen());
+                }
+
+                ui.same_line(220.0);
+
+                if ui.button(None, "Clear") {
+                    editor.setup = board_editor::SetupPosition::empty();
+                }
+
+                ui.same_line(290.0);
+
+                if ui.button(None, "Flip") {
+                    self.vb.is_flipped = !self.vb.is_flipped;
+                }
+
+                for _ in 0..5 {
+                    ui.separator();
+                }
+
+                if ui.button(None, "Done") {
+                    // patterns don't need to be legal positions, a few pieces are enough
+                    if for_search {
+                        editor.setup.white_to_move = editor.side_to_move == 0;
+                        self.search_setup = editor.setup.clone();
+                        self.state = back.clone();
+                        should_break = true;
+                    } else {
+                        match editor.finish(self.game_settings.variant, self.game_settings.chess960)
+                        {
+                            Ok(fen) => {
+                                self.game_settings.position_fen = fen;
+                                self.state = back.clone();
+                                should_break = true;
+                            }
+                            Err(e) => editor.error = Some(e),
+                        }
+                    }
+                }
+
+                ui.same_line(100.0);
+
+                if ui.button(None, "Cancel") {
+                    self.state = back.clone();
+                    should_break = true;
+                }
+
+                if let Some(error) = &editor.error {
+                    ui.label(None, error);
+                }
+            });
+
+            if let Some(piece) = editor.held_piece {
+                self.vb.draw_piece_texture(
+                    piece,
+                    Vec2::from(mouse_position()) - vec2(1.0, 1.0) * self.vb.square_size / 2.0,
+                    self.vb.square_size,
+                );
+            }
+
+            if should_break {
+                break;
+            }
+
+            next_frame().await
+        }
+    }
 
+    async fn try_start_game(&mut self) {
         let (mut timer, max_time) = if let State::TryStartGame { timer, max_time } = self.state {
             (timer, max_time)
         } else {
             unreachable!();
         };
 
-        match game::Game::new(&self.game_settings, &self.lc_data.zb) {
-            Ok(g) => *game = Some(g),
+        match fen::normalize(
+            &self.started_settings.position_fen,
+            self.started_settings.variant,
+            self.started_settings.chess960,
+        ) {
+            Ok(fen) => self.started_settings.position_fen = fen,
+            Err(e) => {
+                self.state = State::TryStartFailed {
+                    reason: e.to_string(),
+                };
+                return;
+            }
+        }
+
+        match game::Game::new(&self.started_settings) {
+            Ok(g) => *GAME.lock().unwrap() = Some(g),
             Err(e) => {
                 self.state = State::TryStartFailed {
                     reason: e.to_string(),
@@ -444,10 +867,11 @@ impl App {
         }
 
         self.vb.draw_ply = 0;
+        self.review = None;
 
         loop {
             clear_background(DARKGRAY);
-            self.vb.draw_board(None, &self.lc_data);
+            self.vb.draw_board(None);
 
             self.ui_window("").ui(&mut ui::root_ui(), |ui| {
                 widgets::Label::new("Starting Game...")
@@ -461,41 +885,39 @@ impl App {
                     .ui(ui);
             });
 
-            if game.as_ref().unwrap().engines[0].is_some()
-                || game.as_ref().unwrap().engines[1].is_some()
+            // the game isn't kept locked while waiting for the next frame
             {
-                match game.as_mut().unwrap().init_engines() {
-                    Err(e) => {
-                        self.state = State::TryStartFailed {
-                            reason: e.to_string(),
-                        };
-                        break;
-                    }
-                    _ => {}
-                }
-            }
+                let mut game = GAME.lock().unwrap();
+                let game = game.as_mut().unwrap();
 
-            if (game.as_ref().unwrap().engine_init_phases[0] == game::EngineInitPhase::End
-                || game.as_ref().unwrap().engines[0].is_none())
-                && (game.as_ref().unwrap().engine_init_phases[1] == game::EngineInitPhase::End
-                    || game.as_ref().unwrap().engines[1].is_none())
-            {
-                self.state = State::InGame;
-                break;
-            } else {
-                timer += time::Duration::from_secs_f32(get_frame_time());
-                if timer >= max_time {
+                if (game.engines[0].is_some() || game.engines[1].is_some())
+                    && let Err(e) = game.init_engines()
+                {
                     self.state = State::TryStartFailed {
-                        reason: "Couldn't Initialize Engines".to_string(),
+                        reason: e.to_string(),
                     };
                     break;
                 }
+
+                if (game.engine_init_phases[0] == game::EngineInitPhase::End
+                    || game.engines[0].is_none())
+                    && (game.engine_init_phases[1] == game::EngineInitPhase::End
+                        || game.engines[1].is_none())
+                {
+                    self.state = State::InGame;
+                    break;
+                }
             }
 
-            self.state = State::TryStartGame {
-                timer: timer,
-                max_time: max_time,
-            };
+            timer += time::Duration::from_secs_f32(get_frame_time());
+            if timer >= max_time {
+                self.state = State::TryStartFailed {
+                    reason: "Couldn't Initialize Engines".to_string(),
+                };
+                break;
+            }
+
+            self.state = State::TryStartGame { timer, max_time };
 
             next_frame().await
         }
@@ -512,7 +934,7 @@ impl App {
 
         loop {
             clear_background(DARKGRAY);
-            self.vb.draw_board(None, &self.lc_data);
+            self.vb.draw_board(None);
 
             self.ui_window("").ui(&mut ui::root_ui(), |ui| {
                 widgets::Label::new(format!("Failed To Start Game: {reason}"))
@@ -554,8 +976,11 @@ impl App {
     async fn in_game(&mut self) {
         static POST_RUN_INFO: LazyLock<Mutex<game::PostRunInfo>> = LazyLock::new(|| {
             Mutex::new(game::PostRunInfo {
-                position: pos::Position::blank(),
+                history: rules::History::new(rules::Board::start()),
                 app_state: State::InGame,
+                draw_offered_by: None,
+                notice: None,
+                records: Vec::new(),
             })
         });
 
@@ -565,12 +990,10 @@ impl App {
                 up_left: None,
                 down_left: None,
                 down_right: None,
+                held_drop: None,
             })
         });
 
-        static LC_DATA_MTX: LazyLock<Mutex<lc::InitData>> =
-            LazyLock::new(|| Mutex::new(lc::init()));
-
         static WTIME_MTX: LazyLock<Mutex<time::Duration>> =
             LazyLock::new(|| Mutex::new(time::Duration::ZERO));
 
@@ -582,22 +1005,32 @@ impl App {
 
         static BREAK_THREAD_LOOP: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(false));
 
+        static PAUSED: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(false));
+
+        static REQUESTED_ACTION: LazyLock<Mutex<Option<game::Action>>> =
+            LazyLock::new(|| Mutex::new(None));
+
         *POST_RUN_INFO.lock().unwrap() = game::PostRunInfo {
-            position: pos::Position::from_fen(&self.game_settings.position_fen, &self.lc_data.zb),
+            history: GAME.lock().unwrap().as_ref().unwrap().history.clone(),
             app_state: State::InGame,
+            draw_offered_by: None,
+            notice: None,
+            records: Vec::new(),
         };
 
         *WTIME_MTX.lock().unwrap() =
-            time::Duration::from_secs(self.game_settings.wtime_s.parse().unwrap());
+            time::Duration::from_secs(self.started_settings.wtime_s.parse().unwrap());
         *BTIME_MTX.lock().unwrap() =
-            time::Duration::from_secs(self.game_settings.btime_s.parse().unwrap());
+            time::Duration::from_secs(self.started_settings.btime_s.parse().unwrap());
 
         let wincrement =
-            time::Duration::from_millis(self.game_settings.wincrement_ms.parse().unwrap());
+            time::Duration::from_millis(self.started_settings.wincrement_ms.parse().unwrap());
         let bincrement =
-            time::Duration::from_millis(self.game_settings.bincrement_ms.parse().unwrap());
+            time::Duration::from_millis(self.started_settings.bincrement_ms.parse().unwrap());
 
         *BREAK_THREAD_LOOP.lock().unwrap() = false;
+        *PAUSED.lock().unwrap() = false;
+        *REQUESTED_ACTION.lock().unwrap() = None;
 
         let game_run_thread = thread::spawn(|| {
             loop {
@@ -608,6 +1041,13 @@ impl App {
                 let mut binding = GAME.lock().unwrap();
                 let game = binding.as_mut().unwrap();
 
+                if let Some(action) = REQUESTED_ACTION.lock().unwrap().take()
+                    && let Some((wtime, btime)) = game.do_action(action)
+                {
+                    *WTIME_MTX.lock().unwrap() = wtime;
+                    *BTIME_MTX.lock().unwrap() = btime;
+                }
+
                 game.wtime = *WTIME_MTX.lock().unwrap();
                 game.btime = *BTIME_MTX.lock().unwrap();
 
@@ -615,7 +1055,9 @@ impl App {
 
                 game.ui_thread_delta_time = *UI_THREAD_DELTA_TIME.lock().unwrap();
 
-                let (p, break_thread) = game.run(&LC_DATA_MTX.lock().unwrap());
+                game.paused = *PAUSED.lock().unwrap();
+
+                let (p, break_thread) = game.run();
                 *POST_RUN_INFO.lock().unwrap() = p;
 
                 if break_thread {
@@ -624,126 +1066,256 @@ impl App {
             }
         });
 
-        let white = match &self.game_settings.white_engine_path {
-            Some(p) => p.name.as_str(),
-            None => "White",
-        };
-
-        let black = match &self.game_settings.black_engine_path {
-            Some(p) => p.name.as_str(),
-            None => "Black",
-        };
+        let (white, black) = self.player_names();
 
-        let mut last_moves_count = 1;
+        let mut last_ply = 0;
 
         let mut clock_text_width = 0.0;
 
+        let mut premove_ply = None;
+        let mut typed_move = String::new();
+        let mut typed_move_error = false;
+        self.vb.premoves.clear();
+
+        let mut show_clock_dialog = false;
+        let mut clock_adjustment_s = "30".to_string();
+
         self.vb
-            .sync_pieces(&POST_RUN_INFO.lock().unwrap().clone().position);
+            .sync_pieces(&POST_RUN_INFO.lock().unwrap().clone().history);
 
         loop {
             *UI_THREAD_DELTA_TIME.lock().unwrap() = time::Duration::from_secs_f32(get_frame_time());
 
             let post_run_info_cpy = POST_RUN_INFO.lock().unwrap().clone();
 
-            events::do_board_mouse_events(
-                &mut self.vb,
-                &post_run_info_cpy.position,
-                &mut GAME_INPUT_SQUARES.lock().unwrap(),
-                [
-                    &self.game_settings.white_engine_path,
-                    &self.game_settings.black_engine_path,
-                ],
-            );
+            let paused = *PAUSED.lock().unwrap();
+
+            if !paused {
+                events::do_board_mouse_events(
+                    &mut self.vb,
+                    &post_run_info_cpy.history,
+                    &mut GAME_INPUT_SQUARES.lock().unwrap(),
+                    [
+                        &self.started_settings.white_engine_path,
+                        &self.started_settings.black_engine_path,
+                    ],
+                );
+            }
+
+            // one premove per ply, the next one has to wait until the engine has replied
+            let human_to_move = [
+                &self.started_settings.white_engine_path,
+                &self.started_settings.black_engine_path,
+            ][post_run_info_cpy.history.current().side()]
+            .is_none();
+
+            if !paused
+                && human_to_move
+                && premove_ply != Some(post_run_info_cpy.history.ply())
+                && let Some(sqs) = events::take_premove(&mut self.vb, &post_run_info_cpy.history)
+            {
+                *GAME_INPUT_SQUARES.lock().unwrap() = sqs;
+                premove_ply = Some(post_run_info_cpy.history.ply());
+            }
 
             let input_sqs = GAME_INPUT_SQUARES.lock().unwrap().clone();
             let (down, up) = (input_sqs.down_left, input_sqs.up_left);
 
             // this is necessary because otherwise user input breaks
-            if down.is_some() && up.is_some() {
+            if (down.is_some() || input_sqs.held_drop.is_some()) && up.is_some() {
                 thread::sleep(time::Duration::from_micros(50));
                 *GAME_INPUT_SQUARES.lock().unwrap() = MouseInputSquares {
                     keys_down: HashSet::new(),
                     down_left: None,
                     up_left: None,
                     down_right: None,
+                    held_drop: None,
                 };
             }
 
             // need to sleep for a tiny bit to avoid piece flickering when making moves
             thread::sleep(time::Duration::from_micros(50));
 
-            events::do_key_events(&mut self.vb, &post_run_info_cpy.position);
+            let post_run_info_cpy = POST_RUN_INFO.lock().unwrap().clone();
+            let history = &post_run_info_cpy.history;
 
-            let mut post_run_info_cpy = POST_RUN_INFO.lock().unwrap().clone();
+            let current_ply = history.ply();
 
-            let current_move_count = post_run_info_cpy.position.moves_opt().len();
+            // the clocks aren't kept locked while waiting for the next frame
+            {
+                let mut wtime_lock = WTIME_MTX.lock().unwrap();
+                let mut btime_lock = BTIME_MTX.lock().unwrap();
+
+                if current_ply < last_ply {
+                    // moves were taken back, the clocks have already been restored by the game thread
+                    last_ply = current_ply;
+                    self.vb.draw_ply = current_ply;
+                    self.vb.sync_pieces(history);
+                    self.vb.selected_piece = None;
+                    self.vb.mouse_input_sqs.down_left = None;
+                    self.vb.arrows.clear();
+                    self.vb.selected_squares.clear();
+                    self.vb.premoves.clear();
+                } else if last_ply != current_ply {
+                    last_ply = current_ply;
+
+                    // the board jumps to the position before the new move if an earlier one was
+                    // being looked at
+                    if self.vb.draw_ply + 1 < current_ply {
+                        self.vb.draw_ply = current_ply - 1;
+                        self.vb.sync_pieces(history);
+                    }
+                    self.vb.draw_ply = current_ply;
 
-            let mut wtime_lock = WTIME_MTX.lock().unwrap();
-            let mut btime_lock = BTIME_MTX.lock().unwrap();
+                    if history.current().white_to_move {
+                        *btime_lock += bincrement;
+                    } else {
+                        *wtime_lock += wincrement;
+                    }
 
-            if last_moves_count != current_move_count {
-                last_moves_count = current_move_count;
-                if self.vb.draw_ply + 1 < post_run_info_cpy.position.moves().len() {
-                    self.vb.sync_pieces(&post_run_info_cpy.position);
+                    self.vb.make_move(
+                        &history.boards[current_ply - 1],
+                        history.moves[current_ply - 1],
+                    );
                 }
-                self.vb.draw_ply = current_move_count - 1;
 
-                if post_run_info_cpy.position.side_to_move() == chess_color::WHITE {
-                    *btime_lock += bincrement;
-                } else {
-                    *wtime_lock += wincrement;
-                }
+                self.state = post_run_info_cpy.app_state.clone();
 
-                if let Some(mov) = post_run_info_cpy.position.move_played() {
-                    self.vb
-                        .make_move(mov, post_run_info_cpy.position.side_to_move());
+                if paused {
+                    // both clocks stay frozen
+                } else if history.current().white_to_move {
+                    *wtime_lock =
+                        wtime_lock.saturating_sub(time::Duration::from_secs_f32(get_frame_time()));
+                } else {
+                    *btime_lock =
+                        btime_lock.saturating_sub(time::Duration::from_secs_f32(get_frame_time()));
                 }
             }
 
-            self.state = post_run_info_cpy.app_state;
+            clear_background(DARKGRAY);
+            self.vb.draw_board(Some(history));
+
+            if paused {
+                draw_rectangle(
+                    self.vb.pos.x,
+                    self.vb.pos.y,
+                    self.vb.board_width(),
+                    self.vb.board_width(),
+                    BLACK.with_alpha(0.5),
+                );
 
-            if post_run_info_cpy.position.side_to_move() == chess_color::WHITE {
-                *wtime_lock =
-                    wtime_lock.saturating_sub(time::Duration::from_secs_f32(get_frame_time()));
-            } else {
-                *btime_lock =
-                    btime_lock.saturating_sub(time::Duration::from_secs_f32(get_frame_time()));
+                let dimensions = measure_text("Paused", Some(&self.font), 60, 1.0);
+                draw_text_ex(
+                    "Paused",
+                    self.vb.pos.x + self.vb.board_width() / 2.0 - dimensions.width / 2.0,
+                    self.vb.pos.y + self.vb.board_width() / 2.0,
+                    TextParams {
+                        font: Some(&self.font),
+                        font_size: 60,
+                        color: WHITE,
+                        ..Default::default()
+                    },
+                );
             }
 
-            drop(wtime_lock);
-            drop(btime_lock);
-
-            clear_background(DARKGRAY);
-            self.vb
-                .draw_board(Some(&mut post_run_info_cpy.position), &self.lc_data);
+            let moves = history.uci_moves();
 
-            let moves = post_run_info_cpy.position.moves();
+            let typed_move_before = typed_move.clone();
 
             self.ui_window(format!("{white}  Vs  {black}").as_str())
                 .ui(&mut ui::root_ui(), |ui| {
-                    let mut idx = 0;
-                    let mut fullmoves = 1;
-                    loop {
-                        if idx >= moves.len() {
-                            break;
-                        }
+                    if human_to_move && !paused {
+                        ui.input_text(hash!(), "Move (SAN Or UCI)", &mut typed_move);
 
-                        let mut fmt = format!("{fullmoves}. {} ({white})", moves[idx].to_uci_fmt());
-                        if idx + 1 < moves.len() {
-                            fmt += format!(", {} ({black})", moves[idx + 1].to_uci_fmt()).as_str();
-                            fullmoves += 1;
+                        if typed_move.len() > 7 {
+                            typed_move.truncate(7);
                         }
 
-                        ui.label(None, &fmt);
+                        if is_key_pressed(KeyCode::Enter) && !typed_move.trim().is_empty() {
+                            match history.current().parse_move(&typed_move) {
+                                Some(mov) => {
+                                    *GAME_INPUT_SQUARES.lock().unwrap() =
+                                        events::input_squares_for(mov);
+                                    typed_move.clear();
+                                    typed_move_error = false;
+                                }
+                                None => typed_move_error = true,
+                            }
+                        }
 
-                        idx += 2;
+                        if typed_move_error {
+                            ui.label(None, "(!) Not A Legal Move");
+                        }
 
                         for _ in 0..5 {
                             ui.separator();
                         }
                     }
+
+                    for (idx, (mov, record)) in
+                        moves.iter().zip(&post_run_info_cpy.records).enumerate()
+                    {
+                        let (fullmoves, white_moved) =
+                            pgn::move_number(&self.started_settings.position_fen, idx);
+
+                        let mut fmt = format!(
+                            "{fullmoves}{} {} ({})  {}  {:.1}s",
+                            if white_moved { "." } else { "..." },
+                            mov,
+                            if white_moved { &white } else { &black },
+                            record.clock.to_clock(),
+                            record.think_time.as_secs_f32()
+                        );
+
+                        if let (Some(depth), Some(score)) = (record.depth, record.score) {
+                            fmt += &format!("  d{depth} {}", score.to_display_fmt());
+                        }
+
+                        ui.label(None, &fmt);
+
+                        if !white_moved {
+                            for _ in 0..5 {
+                                ui.separator();
+                            }
+                        }
+                    }
+                });
+
+            // keys that went into the move box aren't meant for the board
+            if typed_move == typed_move_before {
+                events::do_key_events(&mut self.vb, history);
+            }
+
+            let evals = post_run_info_cpy
+                .records
+                .iter()
+                .map(|r| r.score)
+                .collect::<Vec<_>>();
+
+            // only engines report evals, so there's nothing to plot in human vs human games
+            if evals.iter().any(|e| e.is_some()) {
+                let graph_size = vec2(self.ui_window_size().x, screen_height() / 12.0);
+
+                widgets::Window::new(
+                    hash!(),
+                    self.ui_window_pos() + vec2(0.0, self.ui_window_size().y + 5.0),
+                    graph_size,
+                )
+                .movable(false)
+                .titlebar(false)
+                .ui(&mut ui::root_ui(), |ui| {
+                    if let Some(ply) = eval_graph::draw(
+                        ui,
+                        graph_size - vec2(10.0, 10.0),
+                        &self.started_settings.position_fen,
+                        &evals,
+                        self.vb.draw_ply,
+                    ) {
+                        self.vb.draw_ply = ply;
+                        self.vb.sync_pieces(history);
+                    }
                 });
+            }
 
             let params = TextParams {
                 font: Some(&self.font),
@@ -770,28 +1342,228 @@ impl App {
                 break;
             }
 
-            let wtime_lock = WTIME_MTX.lock().unwrap();
-            let wtime_dimensions = draw_text_ex(
-                wtime_lock.to_clock().as_str(),
-                self.ui_window_pos().x,
-                clock_text_y,
-                TextParams {
-                    color: if post_run_info_cpy.position.side_to_move() == chess_color::WHITE {
-                        if wtime_lock.as_secs() < 60 {
-                            RED
-                        } else {
-                            WHITE
-                        }
-                    } else {
-                        GRAY
-                    },
-                    ..params
-                },
-            );
-
-            drop(wtime_lock);
-
-            draw_rectangle(
+            if widgets::Button::new(if paused { "Resume" } else { "Pause" })
+                .position(vec2(
+                    self.ui_window_pos().x + self.ui_window_size().x / 2.0 - 117.5,
+                    clock_text_y + 50.0,
+                ))
+                .size(vec2(75.0, 30.0))
+                .ui(&mut ui::root_ui())
+            {
+                *PAUSED.lock().unwrap() = !paused;
+            }
+
+            if widgets::Button::new("Clocks")
+                .position(vec2(
+                    self.ui_window_pos().x + self.ui_window_size().x / 2.0 + 52.5,
+                    clock_text_y + 50.0,
+                ))
+                .size(vec2(75.0, 30.0))
+                .ui(&mut ui::root_ui())
+            {
+                show_clock_dialog = !show_clock_dialog;
+            }
+
+            let material_diff = history.current().material_diff();
+
+            let x = if material_diff == 0 {
+                120000.0 // just so the material text is out of sight if no side has the advantage
+            } else if material_diff < 0 {
+                screen_width() - clock_text_width
+            } else {
+                self.ui_window_pos().x
+            };
+
+            let material_dimensions = draw_text_ex(
+                format!("+{}", material_diff.abs()).as_str(),
+                x,
+                clock_text_y + 90.0,
+                TextParams {
+                    font_size: (screen_height() + screen_height()) as u16 / 30,
+                    ..params
+                },
+            );
+
+            // the text is drawn from its baseline, the action buttons go under whatever hangs below
+            let actions_y = clock_text_y
+                + 90.0
+                + (material_dimensions.height - material_dimensions.offset_y)
+                + 10.0;
+
+            // the side the resign and draw buttons act for, if there's a human playing at all
+            let human = match (
+                &self.started_settings.white_engine_path,
+                &self.started_settings.black_engine_path,
+            ) {
+                (None, None) => Some(history.current().side_to_move()),
+                (None, Some(_)) => Some(chess_color::WHITE),
+                (Some(_), None) => Some(chess_color::BLACK),
+                (Some(_), Some(_)) => None,
+            };
+
+            if let Some(human) = human {
+                let mut requested_action = None;
+
+                if widgets::Button::new("Resign")
+                    .position(vec2(
+                        self.ui_window_pos().x + self.ui_window_size().x / 2.0 - 165.0,
+                        actions_y,
+                    ))
+                    .size(vec2(75.0, 30.0))
+                    .ui(&mut ui::root_ui())
+                {
+                    requested_action = Some(game::Action::Resign(human));
+                }
+
+                if widgets::Button::new("Offer Draw")
+                    .position(vec2(
+                        self.ui_window_pos().x + self.ui_window_size().x / 2.0 - 80.0,
+                        actions_y,
+                    ))
+                    .size(vec2(75.0, 30.0))
+                    .ui(&mut ui::root_ui())
+                {
+                    requested_action = Some(game::Action::OfferDraw(human));
+                }
+
+                if widgets::Button::new("Claim Draw")
+                    .position(vec2(
+                        self.ui_window_pos().x + self.ui_window_size().x / 2.0 + 5.0,
+                        actions_y,
+                    ))
+                    .size(vec2(75.0, 30.0))
+                    .ui(&mut ui::root_ui())
+                    && human == history.current().side_to_move()
+                {
+                    requested_action = Some(game::Action::ClaimDraw);
+                }
+
+                if widgets::Button::new("Takeback")
+                    .position(vec2(
+                        self.ui_window_pos().x + self.ui_window_size().x / 2.0 + 90.0,
+                        actions_y,
+                    ))
+                    .size(vec2(75.0, 30.0))
+                    .ui(&mut ui::root_ui())
+                {
+                    requested_action = Some(game::Action::Takeback);
+                }
+
+                // only two humans can have an offer pending, engines answer straight away, and
+                // it's up to the side the offer was made to
+                if post_run_info_cpy
+                    .draw_offered_by
+                    .is_some_and(|by| by != human)
+                {
+                    if widgets::Button::new("Accept")
+                        .position(vec2(
+                            self.ui_window_pos().x + self.ui_window_size().x / 2.0 - 80.0,
+                            actions_y + 40.0,
+                        ))
+                        .size(vec2(75.0, 30.0))
+                        .ui(&mut ui::root_ui())
+                    {
+                        requested_action = Some(game::Action::AcceptDraw(human));
+                    }
+
+                    if widgets::Button::new("Decline")
+                        .position(vec2(
+                            self.ui_window_pos().x + self.ui_window_size().x / 2.0 + 5.0,
+                            actions_y + 40.0,
+                        ))
+                        .size(vec2(75.0, 30.0))
+                        .ui(&mut ui::root_ui())
+                    {
+                        requested_action = Some(game::Action::DeclineDraw(human));
+                    }
+                }
+
+                if requested_action.is_some() {
+                    *REQUESTED_ACTION.lock().unwrap() = requested_action;
+                }
+            }
+
+            if let Some(notice) = post_run_info_cpy.notice {
+                draw_text_ex(
+                    notice,
+                    self.ui_window_pos().x,
+                    actions_y + 100.0,
+                    TextParams {
+                        font_size: (screen_height() + screen_height()) as u16 / 40,
+                        ..params
+                    },
+                );
+            }
+
+            if show_clock_dialog {
+                widgets::Window::new(
+                    hash!(),
+                    self.ui_window_pos(),
+                    vec2(self.ui_window_size().x, 150.0),
+                )
+                .movable(false)
+                .titlebar(true)
+                .label("Adjust Clocks")
+                .ui(&mut ui::root_ui(), |ui| {
+                    ui.input_text(hash!(), "Seconds", &mut clock_adjustment_s);
+
+                    if clock_adjustment_s.len() > 7 {
+                        clock_adjustment_s.truncate(7);
+                    }
+
+                    let adjustment = clock_adjustment_s
+                        .parse::<u64>()
+                        .map(time::Duration::from_secs)
+                        .unwrap_or_default();
+
+                    for _ in 0..5 {
+                        ui.separator();
+                    }
+
+                    if ui.button(None, "+ White") {
+                        *WTIME_MTX.lock().unwrap() += adjustment;
+                    }
+                    ui.same_line(80.0);
+                    if ui.button(None, "- White") {
+                        let mut wtime_lock = WTIME_MTX.lock().unwrap();
+                        *wtime_lock = wtime_lock.saturating_sub(adjustment);
+                    }
+                    ui.same_line(160.0);
+                    if ui.button(None, "+ Black") {
+                        *BTIME_MTX.lock().unwrap() += adjustment;
+                    }
+                    ui.same_line(240.0);
+                    if ui.button(None, "- Black") {
+                        let mut btime_lock = BTIME_MTX.lock().unwrap();
+                        *btime_lock = btime_lock.saturating_sub(adjustment);
+                    }
+
+                    for _ in 0..5 {
+                        ui.separator();
+                    }
+
+                    if ui.button(None, "Done") {
+                        show_clock_dialog = false;
+                    }
+                });
+            }
+
+            let wtime = *WTIME_MTX.lock().unwrap();
+            let wtime_dimensions = draw_text_ex(
+                wtime.to_clock().as_str(),
+                self.ui_window_pos().x,
+                clock_text_y,
+                TextParams {
+                    color: if history.current().white_to_move {
+                        if wtime.as_secs() < 60 { RED } else { WHITE }
+                    } else {
+                        GRAY
+                    },
+                    ..params
+                },
+            );
+
+            draw_rectangle(
                 self.ui_window_pos().x,
                 clock_text_y + 20.0,
                 wtime_dimensions.width,
@@ -799,18 +1571,14 @@ impl App {
                 WHITE,
             );
 
-            let btime_lock = BTIME_MTX.lock().unwrap();
+            let btime = *BTIME_MTX.lock().unwrap();
             let btime_dimensions = draw_text_ex(
-                btime_lock.to_clock().as_str(),
+                btime.to_clock().as_str(),
                 screen_width() - clock_text_width,
                 clock_text_y,
                 TextParams {
-                    color: if post_run_info_cpy.position.side_to_move() == chess_color::BLACK {
-                        if btime_lock.as_secs() < 60 {
-                            RED
-                        } else {
-                            WHITE
-                        }
+                    color: if !history.current().white_to_move {
+                        if btime.as_secs() < 60 { RED } else { WHITE }
                     } else {
                         GRAY
                     },
@@ -818,8 +1586,6 @@ impl App {
                 },
             );
 
-            drop(btime_lock);
-
             draw_rectangle(
                 screen_width() - clock_text_width,
                 clock_text_y + 20.0,
@@ -828,26 +1594,6 @@ impl App {
                 BLACK,
             );
 
-            let material_diff = post_run_info_cpy.position.material_diff();
-
-            let x = if material_diff == 0 {
-                120000.0 // just so the material text is out of sight if no side has the advantage
-            } else if material_diff < 0 {
-                screen_width() - clock_text_width
-            } else {
-                self.ui_window_pos().x
-            };
-
-            draw_text_ex(
-                format!("+{}", material_diff.abs()).as_str(),
-                x,
-                clock_text_y + 90.0,
-                TextParams {
-                    font_size: (screen_height() + screen_height()) as u16 / 30,
-                    ..params
-                },
-            );
-
             clock_text_width = btime_dimensions.width + 10.0;
 
             if let State::GameFinish { .. } = self.state {
@@ -862,22 +1608,145 @@ impl App {
     }
 
     async fn game_finish(&mut self) {
-        let message = if let State::GameFinish { message } = &self.state {
-            message.to_string()
+        let (message, result) = if let State::GameFinish { message, result } = &self.state {
+            (message.to_string(), *result)
         } else {
             unreachable!();
         };
 
+        let mut save_status = String::new();
+
+        self.review = match (
+            &self.started_settings.review_engine_path,
+            self.started_settings.review_movetime_ms.parse::<u64>(),
+        ) {
+            (Some(engine), Ok(ms)) => Some(review::Review::start(
+                &engine.path,
+                time::Duration::from_millis(ms),
+                &GAME.lock().unwrap().as_ref().unwrap().history,
+            )),
+            _ => None,
+        };
+
+        // the game goes into the database now, and again with the marks once it's reviewed
+        let database_path = database::add(&self.game_pgn(&message, result));
+        let mut database_updated = false;
+
+        if let Err(e) = &database_path {
+            save_status = format!("Couldn't Add Game To Database: {e}");
+        }
+
         loop {
             clear_background(DARKGRAY);
-            self.vb.draw_board(
-                Some(&mut GAME.lock().unwrap().as_mut().unwrap().position),
-                &self.lc_data,
-            );
+            self.vb
+                .draw_board(Some(&GAME.lock().unwrap().as_ref().unwrap().history));
             let mut should_break = false;
 
+            if let Some(review) = self.review.as_mut() {
+                review.update();
+            }
+
+            if let (Some(review), Ok(path)) = (&self.review, &database_path)
+                && review.is_done()
+                && review.error().is_none()
+                && !database_updated
+            {
+                database_updated = true;
+
+                if let Err(e) = database::replace(path, &self.game_pgn(&message, result)) {
+                    save_status = format!("Couldn't Update Game In Database: {e}");
+                }
+            }
+
+            // reviewed evals are more even than the ones the players reported while thinking
+            let evals = match &self.review {
+                Some(review) if !review.reviews.is_empty() => review
+                    .reviews
+                    .iter()
+                    .map(|r| Some(r.eval_after))
+                    .collect::<Vec<_>>(),
+                _ => GAME
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .unwrap()
+                    .move_records
+                    .iter()
+                    .map(|r| r.score)
+                    .collect(),
+            };
+            let graph_height = if evals.iter().any(|e| e.is_some()) {
+                80.0
+            } else {
+                0.0
+            };
+
             self.ui_window("").ui(&mut ui::root_ui(), |ui| {
-                widgets::Label::new(&message)
+                if graph_height > 0.0
+                    && let Some(ply) = eval_graph::draw(
+                        ui,
+                        vec2(self.ui_window_size().x - 10.0, graph_height),
+                        &self.started_settings.position_fen,
+                        &evals,
+                        self.vb.draw_ply,
+                    )
+                {
+                    self.vb.draw_ply = ply;
+                    self.vb
+                        .sync_pieces(&GAME.lock().unwrap().as_ref().unwrap().history);
+                }
+
+                if let Some(review) = &self.review {
+                    widgets::Group::new(
+                        hash!(),
+                        vec2(
+                            self.ui_window_size().x - 10.0,
+                            self.ui_window_size().y / 2.0 - 90.0 - graph_height,
+                        ),
+                    )
+                    .position(vec2(0.0, graph_height + 10.0))
+                    .ui(ui, |ui| {
+                        let total = GAME.lock().unwrap().as_ref().unwrap().history.ply();
+
+                        if let Some(e) = review.error() {
+                            ui.label(None, &format!("Review Failed: {e}"));
+                        } else if !review.is_done() {
+                            ui.label(
+                                None,
+                                &format!("Reviewing... {}/{total}", review.reviews.len()),
+                            );
+                        }
+
+                        for (name, color) in
+                            [("White", chess_color::WHITE), ("Black", chess_color::BLACK)]
+                        {
+                            if let Some(accuracy) = review.accuracy(color) {
+                                ui.label(None, &format!("{name} Accuracy: {accuracy:.1}%"));
+                            }
+                        }
+
+                        for (ply, r) in review.reviews.iter().enumerate() {
+                            let (fullmoves, white_moved) =
+                                pgn::move_number(&self.started_settings.position_fen, ply);
+
+                            let mut line = format!(
+                                "{fullmoves}{} {}{} ({})",
+                                if white_moved { "." } else { "..." },
+                                r.san,
+                                r.class.to_symbol(),
+                                r.eval_after.to_display_fmt()
+                            );
+
+                            if let Some(best) = &r.best_move_san {
+                                line += &format!(" best: {best}");
+                            }
+
+                            ui.label(None, &line);
+                        }
+                    });
+                }
+
+                widgets::Label::new(format!("{message}  {}", result.to_pgn_fmt()))
                     .size(vec2(200.0, 100.0))
                     .position(self.ui_window_size() / 2.0 - vec2(100.0, 70.0))
                     .ui(ui);
@@ -903,11 +1772,42 @@ impl App {
                     self.state = State::GameSelection;
                     should_break = true;
                 }
+
+                if widgets::Button::new("Save PGN")
+                    .size(vec2(100.0, 20.0))
+                    .position(self.ui_window_size() / 2.0 - vec2(100.0, -25.0))
+                    .ui(ui)
+                {
+                    save_status = match pgn::save(&self.game_pgn(&message, result)) {
+                        Ok(path) => format!("Saved To {path}"),
+                        Err(e) => format!("Couldn't Save PGN: {e}"),
+                    };
+                }
+
+                if widgets::Button::new("Analyze")
+                    .size(vec2(100.0, 20.0))
+                    .position(self.ui_window_size() / 2.0 - vec2(-20.0, -25.0))
+                    .ui(ui)
+                {
+                    let history = GAME.lock().unwrap().as_ref().unwrap().history.clone();
+
+                    self.state = State::Analysis {
+                        ply: history.ply(),
+                        history,
+                    };
+
+                    should_break = true;
+                }
+
+                widgets::Label::new(&save_status)
+                    .size(vec2(200.0, 20.0))
+                    .position(self.ui_window_size() / 2.0 - vec2(100.0, -55.0))
+                    .ui(ui);
             });
 
             events::do_key_events(
                 &mut self.vb,
-                &GAME.lock().unwrap().as_mut().unwrap().position,
+                &GAME.lock().unwrap().as_ref().unwrap().history,
             );
 
             if should_break {
@@ -917,4 +1817,832 @@ impl App {
             next_frame().await
         }
     }
+
+    async fn analysis(&mut self) {
+        let (mut history, ply) = if let State::Analysis { history, ply } = &self.state {
+            (history.clone(), *ply)
+        } else {
+            unreachable!();
+        };
+
+        let engine_names = self
+            .engines_list
+            .iter()
+            .map(|e| e.name.clone())
+            .collect::<Vec<String>>();
+
+        let mut list = vec!["None"];
+        list.append(&mut engine_names.iter().map(|n| n.as_str()).collect());
+
+        let line_counts = (1..=analysis::MAX_LINES)
+            .map(|n| n.to_string())
+            .collect::<Vec<String>>();
+        let line_counts = line_counts
+            .iter()
+            .map(|n| n.as_str())
+            .collect::<Vec<&str>>();
+
+        // start with the first engine right away, if there is one
+        let mut selected_engine = if self.engines_list.is_empty() { 0 } else { 1 };
+        let mut last_selected_engine = 0;
+        let mut selected_line_count = 2;
+
+        let mut analysis: Option<analysis::Analysis> = None;
+        let mut engine_error: Option<String> = None;
+        let mut show_pv_arrows = false;
+
+        let mut input_sqs = MouseInputSquares {
+            keys_down: HashSet::new(),
+            up_left: None,
+            down_left: None,
+            down_right: None,
+            held_drop: None,
+        };
+
+        self.vb.draw_ply = ply;
+        self.vb.sync_pieces(&history);
+
+        let mut should_break = false;
+
+        loop {
+            if selected_engine != last_selected_engine {
+                last_selected_engine = selected_engine;
+                engine_error = None;
+
+                analysis = if selected_engine == 0 {
+                    None
+                } else {
+                    match analysis::Analysis::new(
+                        &self.engines_list[selected_engine - 1].path,
+                        history.boards[0].chess960,
+                        history.boards[0].variant,
+                    ) {
+                        Ok(a) => Some(a),
+                        Err(e) => {
+                            engine_error = Some(e.to_string());
+                            None
+                        }
+                    }
+                };
+            }
+
+            let analyzed_board = history.boards[self.vb.draw_ply].clone();
+
+            if let Some(a) = analysis.as_mut() {
+                let position_cmd = history.position_cmd_at(self.vb.draw_ply);
+
+                if let Err(e) = a.update(&position_cmd, selected_line_count + 1) {
+                    engine_error = Some(e.to_string());
+                    analysis = None;
+                }
+            }
+
+            events::do_board_mouse_events(&mut self.vb, &history, &mut input_sqs, [&None, &None]);
+
+            if let Some(mov) = game::player_move(&mut input_sqs, history.current()) {
+                self.vb.make_move(history.current(), mov);
+                history.push(mov);
+                self.vb.draw_ply = history.ply();
+            }
+
+            events::do_key_events(&mut self.vb, &history);
+
+            self.vb.engine_arrows = match &analysis {
+                Some(a) => analysis::engine_arrows(
+                    &a.lines,
+                    if show_pv_arrows {
+                        analysis::MAX_PV_ARROWS
+                    } else {
+                        0
+                    },
+                ),
+                None => Vec::new(),
+            };
+
+            clear_background(DARKGRAY);
+            self.vb.draw_board(Some(&history));
+
+            self.ui_window("Analysis").ui(&mut ui::root_ui(), |ui| {
+                ui.combo_box(hash!(), "Engine", &list, &mut selected_engine);
+                ui.combo_box(hash!(), "Lines", &line_counts, &mut selected_line_count);
+                ui.checkbox(hash!(), "Show Best Line Arrows", &mut show_pv_arrows);
+
+                for _ in 0..10 {
+                    ui.separator();
+                }
+
+                match (&analysis, &engine_error) {
+                    (_, Some(e)) => ui.label(None, &format!("Engine Error: {e}")),
+                    (None, None) => ui.label(None, "No Engine Selected"),
+                    (Some(a), None) if !a.is_ready() => ui.label(None, "Starting Engine..."),
+                    (Some(a), None) => {
+                        for line in &a.lines {
+                            // engines report scores for the side to move, show them for white
+                            let score = match line.score {
+                                Some(score) if analyzed_board.white_to_move => {
+                                    score.to_display_fmt()
+                                }
+                                Some(score) => score.negated().to_display_fmt(),
+                                None => "?".to_string(),
+                            };
+
+                            ui.label(
+                                None,
+                                &format!(
+                                    "{score} (depth {})  {}",
+                                    line.depth,
+                                    analyzed_board.line_to_san(&line.pv, 6)
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                for _ in 0..10 {
+                    ui.separator();
+                }
+
+                // drops every move after the one being looked at, so a different line can be tried
+                if ui.button(None, "Play From Here") {
+                    history.truncate(self.vb.draw_ply);
+                    self.vb.sync_pieces(&history);
+                }
+
+                ui.same_line(140.0);
+
+                if ui.button(None, "Back") {
+                    self.state = State::Home;
+                    should_break = true;
+                }
+            });
+
+            if should_break {
+                self.vb.engine_arrows.clear();
+                break;
+            }
+
+            next_frame().await
+        }
+    }
+
+    async fn puzzles(&mut self) {
+        let (all_puzzles, load_error) = match puzzles::load_all() {
+            Ok(p) => (p, None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+
+        let mut history = puzzles::History::load();
+        let mut session: Option<puzzles::Session> = None;
+        let mut broken = HashSet::new(); // puzzles whose fen or solution can't be played
+        let mut shown = Vec::new(); // ids of every puzzle shown so far, skipped ones too
+        let mut puzzle_error: Option<String> = None;
+        let mut save_error: Option<String> = None;
+        let mut want_next = true;
+
+        let mut input_sqs = MouseInputSquares {
+            keys_down: HashSet::new(),
+            up_left: None,
+            down_left: None,
+            down_right: None,
+            held_drop: None,
+        };
+
+        let mut should_break = false;
+
+        loop {
+            while want_next {
+                session = None;
+
+                let Some(puzzle) = history.next_puzzle(&all_puzzles, &broken, &shown) else {
+                    want_next = false;
+                    break;
+                };
+
+                match puzzles::Session::new(puzzle) {
+                    Ok(s) => {
+                        self.vb.is_flipped = s.solver == 1;
+                        self.vb.draw_ply = 0;
+                        self.vb.sync_pieces(&s.history);
+                        shown.push(s.puzzle.id.clone());
+                        session = Some(s);
+                        want_next = false;
+                    }
+                    Err(e) => {
+                        puzzle_error = Some(format!("Puzzle {} Skipped: {e}", puzzle.id));
+                        broken.insert(puzzle.id.clone());
+                    }
+                }
+            }
+
+            if let Some(s) = session.as_mut() {
+                if let Some(mov) = s.update(get_frame_time()) {
+                    self.vb
+                        .make_move(&s.history.boards[s.history.ply() - 1], mov);
+                    self.vb.draw_ply = s.history.ply();
+                }
+
+                if s.is_solvers_turn() {
+                    events::do_board_mouse_events(
+                        &mut self.vb,
+                        &s.history,
+                        &mut input_sqs,
+                        [&None, &None],
+                    );
+
+                    if let Some(mov) = game::player_move(&mut input_sqs, s.history.current())
+                        && s.try_move(mov)
+                    {
+                        self.vb
+                            .make_move(&s.history.boards[s.history.ply() - 1], mov);
+                        self.vb.draw_ply = s.history.ply();
+                    }
+                }
+
+                if let (Some(solved), false) = (s.result(), s.recorded) {
+                    s.recorded = true;
+                    save_error = history
+                        .record(&s.puzzle, solved)
+                        .err()
+                        .map(|e| e.to_string());
+                }
+
+                events::do_key_events(&mut self.vb, &s.history);
+            }
+
+            clear_background(DARKGRAY);
+            self.vb.draw_board(session.as_ref().map(|s| &s.history));
+
+            self.ui_window("Puzzles").ui(&mut ui::root_ui(), |ui| {
+                ui.label(None, &format!("Your Rating: {:.0}", history.rating()));
+
+                for _ in 0..10 {
+                    ui.separator();
+                }
+
+                match (&load_error, &session) {
+                    (Some(e), _) => ui.label(None, &format!("Couldn't Load Puzzles: {e}")),
+                    (None, None) => ui.label(None, "No Puzzles Found In assets/puzzles/"),
+                    (None, Some(s)) => {
+                        ui.label(
+                            None,
+                            &format!("Puzzle {} (Rated {})", s.puzzle.id, s.puzzle.rating),
+                        );
+
+                        if !s.puzzle.themes.is_empty() {
+                            ui.label(None, &format!("Themes: {}", s.puzzle.themes.join(", ")));
+                        }
+
+                        let to_move = if s.solver == 0 { "White" } else { "Black" };
+
+                        ui.label(
+                            None,
+                            &match s.status {
+                                puzzles::Status::Solved if s.mistakes == 0 => "Solved!".to_string(),
+                                puzzles::Status::Solved => "Solved, But Not First Try".to_string(),
+                                puzzles::Status::SolutionShown => "Solution Shown".to_string(),
+                                puzzles::Status::Solving if s.last_move_wrong => {
+                                    "Wrong Move, Try Again".to_string()
+                                }
+                                puzzles::Status::Solving => format!("{to_move} To Move"),
+                            },
+                        );
+                    }
+                }
+
+                if let Some(e) = &puzzle_error {
+                    ui.label(None, &format!("(!) {e}"));
+                }
+
+                if let Some(e) = &save_error {
+                    ui.label(None, &format!("(!) Couldn't Save Puzzle History: {e}"));
+                }
+
+                for _ in 0..10 {
+                    ui.separator();
+                }
+
+                if let Some(s) = session.as_mut() {
+                    if s.status == puzzles::Status::Solving && ui.button(None, "Show Solution") {
+                        s.show_solution();
+                        self.vb.draw_ply = s.history.ply();
+                        self.vb.sync_pieces(&s.history);
+                    }
+
+                    ui.same_line(140.0);
+                }
+
+                if ui.button(None, "Next Puzzle") {
+                    puzzle_error = None;
+                    want_next = true;
+                }
+
+                ui.same_line(280.0);
+
+                if ui.button(None, "Back") {
+                    self.state = State::Home;
+                    should_break = true;
+                }
+
+                for _ in 0..10 {
+                    ui.separator();
+                }
+
+                ui.label(None, "Recent Puzzles:");
+
+                for attempt in history.attempts.iter().rev().take(10) {
+                    ui.label(
+                        None,
+                        &format!(
+                            "{}  {}  {:.0}",
+                            attempt.id,
+                            if attempt.solved { "Solved" } else { "Failed" },
+                            attempt.rating_after
+                        ),
+                    );
+                }
+            });
+
+            if should_break {
+                self.vb.is_flipped = false;
+                self.vb.draw_ply = 0;
+                self.vb.sync_board(&rules::Board::start());
+                break;
+            }
+
+            next_frame().await
+        }
+    }
+
+    async fn repertoire(&mut self) {
+        let files = repertoire::list_files();
+        let file_names = files.iter().map(|f| f.as_str()).collect::<Vec<&str>>();
+
+        let mut progress = repertoire::Progress::load();
+        let mut selected_file = 0;
+        let mut selected_side = 0;
+        let mut loaded: Option<(usize, usize)> = None; // file and side the lines are for
+        let mut lines: Result<Vec<repertoire::Line>, String> = Ok(Vec::new());
+        let mut drill: Option<repertoire::Drill> = None;
+        let mut save_error: Option<String> = None;
+        let mut want_next = false;
+
+        let mut input_sqs = MouseInputSquares {
+            keys_down: HashSet::new(),
+            up_left: None,
+            down_left: None,
+            down_right: None,
+            held_drop: None,
+        };
+
+        let mut should_break = false;
+
+        loop {
+            let user = selected_side; // 0 for white
+
+            if !files.is_empty() && loaded != Some((selected_file, selected_side)) {
+                loaded = Some((selected_file, selected_side));
+                lines = repertoire::load(&files[selected_file]);
+                drill = None;
+                want_next = true;
+            }
+
+            if want_next {
+                want_next = false;
+
+                drill = lines.as_ref().ok().and_then(|lines| {
+                    repertoire::next_line(
+                        lines,
+                        &progress,
+                        &files[selected_file],
+                        user,
+                        drill.as_ref().map(|d| &d.line),
+                    )
+                    .map(|line| repertoire::Drill::new(line, user))
+                });
+
+                if let Some(d) = &drill {
+                    self.vb.is_flipped = user == 1;
+                    self.vb.draw_ply = 0;
+                    self.vb.sync_pieces(&d.history);
+                }
+            }
+
+            if let Some(d) = drill.as_mut() {
+                if let Some(mov) = d.update(get_frame_time()) {
+                    self.vb
+                        .make_move(&d.history.boards[d.history.ply() - 1], mov);
+                    self.vb.draw_ply = d.history.ply();
+                }
+
+                if d.is_users_turn() {
+                    events::do_board_mouse_events(
+                        &mut self.vb,
+                        &d.history,
+                        &mut input_sqs,
+                        [&None, &None],
+                    );
+
+                    if let Some(mov) = game::player_move(&mut input_sqs, d.history.current())
+                        && d.try_move(mov, lines.as_deref().unwrap_or(&[]))
+                    {
+                        self.vb
+                            .make_move(&d.history.boards[d.history.ply() - 1], mov);
+                        self.vb.draw_ply = d.history.ply();
+                    }
+                }
+
+                if d.done && !d.recorded {
+                    d.recorded = true;
+                    save_error = progress
+                        .record(
+                            &repertoire::key(&files[selected_file], user, &d.line),
+                            d.mistakes == 0,
+                        )
+                        .err()
+                        .map(|e| e.to_string());
+                }
+
+                events::do_key_events(&mut self.vb, &d.history);
+            }
+
+            clear_background(DARKGRAY);
+            self.vb.draw_board(drill.as_ref().map(|d| &d.history));
+
+            self.ui_window("Opening Trainer")
+                .ui(&mut ui::root_ui(), |ui| {
+                    if files.is_empty() {
+                        ui.label(None, "No PGN Files Found In assets/repertoires/");
+                    } else {
+                        ui.combo_box(hash!(), "Repertoire", &file_names, &mut selected_file);
+                        ui.combo_box(hash!(), "Train As", &["White", "Black"], &mut selected_side);
+                    }
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    match (&lines, &drill) {
+                        (Err(e), _) => ui.label(None, &format!("Couldn't Read Repertoire: {e}")),
+                        (Ok(_), None) if !files.is_empty() => {
+                            ui.label(None, "No Lines With Moves To Train For This Side")
+                        }
+                        (Ok(_), None) => {}
+                        (Ok(lines), Some(d)) => {
+                            let keys = lines
+                                .iter()
+                                .map(|l| repertoire::key(&files[selected_file], user, l))
+                                .collect::<Vec<String>>();
+
+                            ui.label(
+                                None,
+                                &format!(
+                                    "{} Lines, {} Due For Review",
+                                    lines.len(),
+                                    progress.due_count(&keys)
+                                ),
+                            );
+                            ui.label(
+                                None,
+                                &match (&d.hint, d.done) {
+                                    (_, true) if d.mistakes == 0 => "Line Complete!".to_string(),
+                                    (_, true) => "Line Complete, It'll Come Back Soon".to_string(),
+                                    (Some(hint), false) => {
+                                        format!("Not In Your Repertoire, You Play {hint} Here")
+                                    }
+                                    (None, false) => "Play Your Prepared Move".to_string(),
+                                },
+                            );
+
+                            if d.done {
+                                ui.label(None, &d.line.sans);
+                            }
+                        }
+                    }
+
+                    if let Some(e) = &save_error {
+                        ui.label(None, &format!("(!) Couldn't Save Progress: {e}"));
+                    }
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    if ui.button(None, "Next Line") {
+                        want_next = true;
+                    }
+
+                    ui.same_line(140.0);
+
+                    if ui.button(None, "Back") {
+                        self.state = State::Home;
+                        should_break = true;
+                    }
+                });
+
+            if should_break {
+                self.vb.is_flipped = false;
+                self.vb.draw_ply = 0;
+                self.vb.sync_board(&rules::Board::start());
+                break;
+            }
+
+            next_frame().await
+        }
+    }
+
+    async fn endgames(&mut self) {
+        let drill_names = endgames::DRILLS
+            .iter()
+            .map(|d| d.name)
+            .collect::<Vec<&str>>();
+
+        let mut list = vec!["None"];
+        list.append(
+            &mut self
+                .engines_list
+                .iter()
+                .map(|e| e.name.as_str())
+                .collect::<Vec<&str>>(),
+        );
+
+        let mut selected_drill = 0;
+        let mut last_selected_drill = None;
+        // start with the first engine right away, if there is one
+        let mut defender = if self.engines_list.is_empty() { 0 } else { 1 };
+
+        let mut should_break = false;
+
+        loop {
+            let drill = &endgames::DRILLS[selected_drill];
+
+            if last_selected_drill != Some(selected_drill) {
+                last_selected_drill = Some(selected_drill);
+                self.vb.draw_ply = 0;
+                self.vb.sync_board(
+                    &rules::Board::from_fen(drill.fen, Variant::Standard, false).unwrap(),
+                );
+            }
+
+            clear_background(DARKGRAY);
+            self.vb.draw_board(None);
+
+            self.ui_window("Endgame Drills")
+                .ui(&mut ui::root_ui(), |ui| {
+                    ui.combo_box(hash!(), "Drill", &drill_names, &mut selected_drill);
+                    ui.combo_box(hash!(), "Defending Engine", &list, &mut defender);
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    ui.label(None, drill.description);
+
+                    if defender == 0 {
+                        ui.label(None, "(Without an engine you play both sides)");
+                    }
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    if ui.button(None, "Start") {
+                        let practicing_white = drill.fen.split_whitespace().nth(1) == Some("w");
+                        let engine = if defender == 0 {
+                            None
+                        } else {
+                            Some(self.engines_list[defender - 1].clone())
+                        };
+
+                        // the drill's settings are its own, the ones chosen for normal games are
+                        // left as they were
+                        self.started_settings = game::Settings {
+                            position_fen: drill.fen.to_string(),
+                            white_engine_path: if practicing_white {
+                                None
+                            } else {
+                                engine.clone()
+                            },
+                            black_engine_path: if practicing_white { engine } else { None },
+                            max_engine_think_time_s: "5".to_string(),
+                            wtime_s: "900".to_string(),
+                            btime_s: "900".to_string(),
+                            wincrement_ms: "0".to_string(),
+                            bincrement_ms: "0".to_string(),
+                            review_engine_path: None,
+                            chess960: false,
+                            variant: Variant::Standard,
+                            white_engine_elo: String::new(),
+                            black_engine_elo: String::new(),
+                            goal: Some(drill.goal),
+                            ..self.game_settings.clone()
+                        };
+
+                        self.state = State::TryStartGame {
+                            timer: time::Duration::ZERO,
+                            max_time: time::Duration::from_secs(15),
+                        };
+                        should_break = true;
+                    }
+
+                    ui.same_line(140.0);
+
+                    if ui.button(None, "Back") {
+                        self.state = State::Home;
+                        should_break = true;
+                    }
+                });
+
+            if should_break {
+                if let State::Home = self.state {
+                    self.vb.sync_board(&rules::Board::start());
+                }
+                break;
+            }
+
+            next_frame().await
+        }
+    }
+
+    async fn database(&mut self) {
+        // games shown at most, the list gets slow past this
+        const MAX_LISTED: usize = 200;
+
+        let (mut entries, mut load_errors) = database::load_all();
+        let mut import_path = String::new();
+        let mut import_status = String::new();
+
+        let mut search_status = String::new();
+        let mut found: Option<Vec<(usize, usize)>> = None; // (entry, ply)
+
+        let mut should_break = false;
+
+        loop {
+            clear_background(DARKGRAY);
+            if self.search_kind == 1 {
+                self.vb.draw_board(None);
+            } else {
+                self.vb.draw_setup(&self.search_setup);
+            }
+
+            let matching = (0..entries.len())
+                .filter(|idx| self.database_filter.matches(&entries[*idx]))
+                .collect::<Vec<usize>>();
+            let mut opened = None;
+            let mut reload = false;
+
+            self.ui_window("Game Database")
+                .ui(&mut ui::root_ui(), |ui| {
+                    let filter = &mut self.database_filter;
+                    ui.input_text(hash!(), "Player Or Engine", &mut filter.player);
+                    ui.combo_box(hash!(), "Result", &database::RESULTS, &mut filter.result);
+                    ui.input_text(hash!(), "Date (yyyy.mm.dd)", &mut filter.date);
+                    ui.input_text(hash!(), "Opening (ECO, Name Or Moves)", &mut filter.opening);
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    ui.combo_box(hash!(), "Search By", &search::KINDS, &mut self.search_kind);
+
+                    if self.search_kind == 1 {
+                        ui.input_text(hash!(), "Material (like KRPvKR)", &mut self.search_material);
+                    } else if ui.button(None, "Draw Position") {
+                        self.state = State::BoardEditor { for_search: true };
+                        should_break = true;
+                    }
+
+                    if ui.button(None, "Search") {
+                        let drawn = self
+                            .search_setup
+                            .board
+                            .iter()
+                            .flatten()
+                            .any(|p| p.is_some());
+                        let query = match self.search_kind {
+                            1 => search::parse_material(&self.search_material)
+                                .map(search::Query::Material),
+                            kind if drawn => Some(search::Query::from_setup(
+                                &self.search_setup,
+                                kind == 0,
+                                &self.zb,
+                            )),
+                            _ => None,
+                        };
+
+                        match query {
+                            Some(query) => {
+                                let results = search::search(&entries, &matching, &query, &self.zb);
+                                search_status = format!("{} Games Found", results.len());
+                                found = Some(results);
+                            }
+                            None if self.search_kind == 1 => {
+                                search_status = "Invalid Material".to_string();
+                            }
+                            None => search_status = "Draw Some Pieces First".to_string(),
+                        }
+                    }
+
+                    if found.is_some() {
+                        ui.same_line(70.0);
+
+                        if ui.button(None, "Clear Search") {
+                            found = None;
+                            search_status.clear();
+                        }
+                    }
+
+                    ui.label(None, &search_status);
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    ui.label(
+                        None,
+                        &format!("{} Of {} Games", matching.len(), entries.len()),
+                    );
+
+                    for e in &load_errors {
+                        ui.label(None, &format!("(!) Couldn't Read {e}"));
+                    }
+
+                    // search results open at the ply the position comes up, the rest at the end
+                    let listed = match &found {
+                        Some(results) => results.clone(),
+                        None => matching
+                            .iter()
+                            .map(|idx| (*idx, entries[*idx].game.main_line.len()))
+                            .collect(),
+                    };
+
+                    widgets::Group::new(hash!(), vec2(self.ui_window_size().x - 10.0, 250.0)).ui(
+                        ui,
+                        |ui| {
+                            for (idx, ply) in listed.iter().take(MAX_LISTED) {
+                                let entry = &entries[*idx];
+                                let text = if found.is_some() {
+                                    let (n, white) =
+                                        pgn::move_number(&entry.game.start.to_fen(), *ply);
+                                    let dots = if white { "." } else { "..." };
+                                    format!("{}  (at {n}{dots})", entry.to_display_fmt())
+                                } else {
+                                    entry.to_display_fmt()
+                                };
+
+                                if ui.button(None, text) {
+                                    opened = Some((*idx, *ply));
+                                }
+                            }
+                        },
+                    );
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    ui.input_text(hash!(), "PGN File To Import", &mut import_path);
+
+                    if ui.button(None, "Import") {
+                        import_status = match database::import(&import_path) {
+                            Ok(n) => {
+                                reload = true;
+                                format!("Imported {n} Games")
+                            }
+                            Err(e) => format!("Couldn't Import: {e}"),
+                        };
+                    }
+
+                    ui.label(None, &import_status);
+
+                    for _ in 0..10 {
+                        ui.separator();
+                    }
+
+                    if ui.button(None, "Back") {
+                        self.state = State::Home;
+                        should_break = true;
+                    }
+                });
+
+            if let Some((idx, ply)) = opened {
+                self.state = State::Analysis {
+                    history: entries[idx].game.history(),
+                    ply,
+                };
+                should_break = true;
+            }
+
+            if reload {
+                (entries, load_errors) = database::load_all();
+                found = None;
+                search_status.clear();
+            }
+
+            if should_break {
+                break;
+            }
+
+            next_frame().await
+        }
+    }
 }
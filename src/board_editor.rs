@@ -0,0 +1,187 @@
+use libchess::pos;
+
+use crate::{
+    fen::{self, FenError, FenField},
+    rules,
+    variant::Variant,
+};
+
+// piece indices are the same as the texture indices, white pieces first
+pub const PIECE_CHARS: [char; 12] = ['P', 'N', 'B', 'R', 'Q', 'K', 'p', 'n', 'b', 'r', 'q', 'k'];
+
+/// a position that's being set up by hand and may not be legal yet
+#[derive(Clone)]
+pub struct SetupPosition {
+    pub board: [[Option<usize>; 8]; 8], // [rank][file]
+    pub white_to_move: bool,
+    pub castling: [bool; 4], // K, Q, k, q
+    pub en_passant: Option<(pos::File, pos::Rank)>,
+    pub halfmoves: u32,
+    pub fullmoves: u32,
+}
+
+impl SetupPosition {
+    pub fn empty() -> Self {
+        Self {
+            board: [[None; 8]; 8],
+            white_to_move: true,
+            castling: [false; 4],
+            en_passant: None,
+            halfmoves: 0,
+            fullmoves: 1,
+        }
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for r in (0..8).rev() {
+            let mut empty = 0;
+
+            for f in 0..8 {
+                match self.board[r][f] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen += &empty.to_string();
+                            empty = 0;
+                        }
+                        fen.push(PIECE_CHARS[piece]);
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                fen += &empty.to_string();
+            }
+
+            if r > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen += if self.white_to_move { " w " } else { " b " };
+
+        let castling = ['K', 'Q', 'k', 'q']
+            .iter()
+            .zip(self.castling)
+            .filter(|(_, allowed)| *allowed)
+            .map(|(c, _)| *c)
+            .collect::<String>();
+
+        fen += if castling.is_empty() {
+            "-"
+        } else {
+            castling.as_str()
+        };
+
+        fen += &match self.en_passant {
+            Some((f, r)) => format!(" {}{} ", (f as u8 + b'a') as char, r + 1),
+            None => " - ".to_string(),
+        };
+
+        fen + &format!("{} {}", self.halfmoves, self.fullmoves)
+    }
+
+    pub fn piece_on(&self, f: pos::File, r: pos::Rank) -> Option<usize> {
+        self.board[r as usize][f as usize]
+    }
+
+    pub fn set_piece(&mut self, f: pos::File, r: pos::Rank, piece: Option<usize>) {
+        self.board[r as usize][f as usize] = piece;
+    }
+
+    /// checks that a game can be played from the position
+    pub fn validate(&self, variant: Variant, chess960: bool) -> Result<(), FenError> {
+        rules::Board::from_fen(&self.to_fen(), variant, chess960).map(|_| ())
+    }
+}
+
+pub fn parse_square(sq: &str) -> Option<(pos::File, pos::Rank)> {
+    let mut chars = sq.chars();
+    let f = chars.next().filter(|c| ('a'..='h').contains(c))? as u8 - b'a';
+    let r = chars.next().filter(|c| ('1'..='8').contains(c))? as u8 - b'1';
+
+    if chars.next().is_some() {
+        return None;
+    }
+
+    Some((f as pos::File, r as pos::Rank))
+}
+
+/// everything the editor screen keeps around between frames
+pub struct Editor {
+    pub setup: SetupPosition,
+    pub held_piece: Option<usize>,
+    pub side_to_move: usize,
+    pub en_passant_text: String,
+    pub halfmoves_text: String,
+    pub fullmoves_text: String,
+    pub fen_text: String,
+    pub error: Option<String>,
+}
+
+impl Editor {
+    pub fn new(fen: &str) -> Self {
+        let mut editor = Self {
+            setup: fen::parse(pos::START_FEN).unwrap(),
+            held_piece: None,
+            side_to_move: 0,
+            en_passant_text: String::new(),
+            halfmoves_text: String::new(),
+            fullmoves_text: String::new(),
+            fen_text: fen.to_string(),
+            error: None,
+        };
+
+        editor.load(fen);
+        editor
+    }
+
+    /// replaces the setup with the one in `fen`, keeps the current one if it can't be read
+    pub fn load(&mut self, fen: &str) {
+        match fen::parse(fen) {
+            Ok(setup) => {
+                self.setup = setup;
+                self.error = None;
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+            }
+        }
+
+        self.side_to_move = if self.setup.white_to_move { 0 } else { 1 };
+        self.en_passant_text = match self.setup.en_passant {
+            Some((f, r)) => format!("{}{}", (f as u8 + b'a') as char, r + 1),
+            None => "-".to_string(),
+        };
+        self.halfmoves_text = self.setup.halfmoves.to_string();
+        self.fullmoves_text = self.setup.fullmoves.to_string();
+    }
+
+    /// reads back the text fields into the setup, then checks the whole position
+    pub fn finish(&mut self, variant: Variant, chess960: bool) -> Result<String, String> {
+        self.setup.white_to_move = self.side_to_move == 0;
+
+        self.setup.en_passant = match self.en_passant_text.trim() {
+            "" | "-" => None,
+            sq => Some(parse_square(sq).ok_or_else(|| {
+                FenError::new(FenField::EnPassant, format!("'{sq}' Is Not A Square")).to_string()
+            })?),
+        };
+
+        self.setup.halfmoves = self.halfmoves_text.trim().parse().map_err(|_| {
+            FenError::new(FenField::HalfmoveClock, "Not A Non-Negative Number").to_string()
+        })?;
+
+        self.setup.fullmoves = self.fullmoves_text.trim().parse().map_err(|_| {
+            FenError::new(FenField::FullmoveNumber, "Not A Non-Negative Number").to_string()
+        })?;
+
+        self.setup
+            .validate(variant, chess960)
+            .map_err(|e| e.to_string())?;
+
+        Ok(self.setup.to_fen())
+    }
+}
@@ -0,0 +1,67 @@
+// chess960 start positions, castling with the king and rooks on any files is up to `rules`
+
+use std::time;
+
+const POSITIONS: usize = 960;
+
+// where the knights go among the five squares left after the bishops and queen are placed
+const KNIGHT_SQUARES: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// the back rank of start position `n` (0 to 959) using Scharnagl's numbering, 518 is the usual
+/// start position
+fn back_rank(n: usize) -> [char; 8] {
+    let mut rank = [' '; 8];
+
+    let (n, light_bishop) = (n / 4, n % 4);
+    rank[light_bishop * 2 + 1] = 'B';
+
+    let (n, dark_bishop) = (n / 4, n % 4);
+    rank[dark_bishop * 2] = 'B';
+
+    let empty = |rank: &[char; 8]| (0..8).filter(|f| rank[*f] == ' ').collect::<Vec<usize>>();
+
+    let (n, queen) = (n / 6, n % 6);
+    rank[empty(&rank)[queen]] = 'Q';
+
+    let (a, b) = KNIGHT_SQUARES[n];
+    let squares = empty(&rank);
+    rank[squares[a]] = 'N';
+    rank[squares[b]] = 'N';
+
+    // the king always ends up between the rooks
+    for (f, piece) in empty(&rank).into_iter().zip(['R', 'K', 'R']) {
+        rank[f] = piece;
+    }
+
+    rank
+}
+
+/// both sides can castle with both rooks, KQkq is enough to say which since there are only two
+pub fn start_fen(n: usize) -> String {
+    let white = back_rank(n % POSITIONS).iter().collect::<String>();
+
+    format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{white} w KQkq - 0 1",
+        white.to_lowercase()
+    )
+}
+
+pub fn random_start_fen() -> String {
+    let nanos = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as usize;
+
+    start_fen(nanos % POSITIONS)
+}
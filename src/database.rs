@@ -0,0 +1,176 @@
+// every game played, reviewed or imported, kept as pgn files in DATABASE_DIR
+//
+// finished games get a file of their own (rewritten once their review is done), imported files are
+// copied in as they are and can hold any number of games. Chess960 and variant games are kept too,
+// their Variant tag says which rules to read them back with
+
+use std::{fs, io, path, time};
+
+use libchess::pos;
+
+use crate::{openings, pgn, variant::Variant};
+
+const DATABASE_DIR: &str = "assets/database/";
+
+pub const RESULTS: [&str; 5] = ["Any", "1-0", "0-1", "1/2-1/2", "*"];
+
+pub struct Entry {
+    pub game: pgn::ReadGame,
+    pub sans: String, // the main line, for searching by opening moves
+    // named from the moves, for games without ECO and Opening tags
+    pub opening: Option<&'static openings::Opening>,
+}
+
+impl Entry {
+    pub fn to_display_fmt(&self) -> String {
+        let tag = |name| self.game.tag(name).unwrap_or("?");
+
+        let mut fmt = format!(
+            "{} - {}  {}  {}",
+            tag("White"),
+            tag("Black"),
+            tag("Result"),
+            tag("Date")
+        );
+
+        if let Some(variant) = self.game.tag("Variant") {
+            fmt += &format!("  {variant}");
+        }
+
+        fmt
+    }
+}
+
+/// a new file that isn't taken yet, named after the time it was made
+fn new_path() -> String {
+    let secs = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut path = format!("{DATABASE_DIR}{secs}.pgn");
+    let mut n = 1;
+
+    while path::Path::new(&path).exists() {
+        path = format!("{DATABASE_DIR}{secs}-{n}.pgn");
+        n += 1;
+    }
+
+    path
+}
+
+/// returns the path the game was stored at, so it can be replaced later
+pub fn add(pgn: &str) -> io::Result<String> {
+    fs::create_dir_all(DATABASE_DIR)?;
+
+    let path = new_path();
+    fs::write(&path, pgn)?;
+
+    Ok(path)
+}
+
+pub fn replace(path: &str, pgn: &str) -> io::Result<()> {
+    fs::write(path, pgn)
+}
+
+/// copies the pgn file at `path` into the database, returning how many games it had, files with
+/// a game that can't be read aren't imported at all
+pub fn import(path: &str) -> Result<usize, String> {
+    let text = fs::read_to_string(path.trim()).map_err(|e| e.to_string())?;
+    let games = pgn::read(&text)?.len();
+
+    if games == 0 {
+        return Err("No Games In File".to_string());
+    }
+
+    add(&text).map_err(|e| e.to_string())?;
+
+    Ok(games)
+}
+
+/// every game in the database, along with the files that couldn't be read
+pub fn load_all() -> (Vec<Entry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    let Ok(dir) = fs::read_dir(DATABASE_DIR) else {
+        return (entries, errors);
+    };
+
+    let mut paths = dir
+        .filter_map(|e| Some(e.ok()?.path().to_str()?.to_string()))
+        .filter(|p| p.ends_with(".pgn"))
+        .collect::<Vec<String>>();
+    paths.sort();
+
+    for path in paths {
+        let games = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| pgn::read(&text));
+
+        match games {
+            Ok(games) => {
+                for game in games {
+                    let sans = game.history().sans().join(" ");
+                    let opening = if game.start.variant == Variant::Standard
+                        && !game.start.chess960
+                        && game.start.to_fen() == pos::START_FEN
+                    {
+                        openings::classify(&sans)
+                    } else {
+                        None
+                    };
+
+                    entries.push(Entry {
+                        game,
+                        sans,
+                        opening,
+                    });
+                }
+            }
+            Err(e) => errors.push(format!("{path}: {e}")),
+        }
+    }
+
+    (entries, errors)
+}
+
+#[derive(Default, Clone, PartialEq)]
+pub struct Filter {
+    pub player: String,  // white or black, engines included
+    pub result: usize,   // index into RESULTS
+    pub date: String,    // part of the date tag, like "2024.05"
+    pub opening: String, // the ECO code, the opening's name, or the first moves ("e4 c5")
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let contains =
+            |value: &str, text: &str| value.to_lowercase().contains(&text.to_lowercase());
+        let has = |tag: &str, text: &str| entry.game.tag(tag).is_some_and(|v| contains(v, text));
+
+        let player = self.player.trim();
+        let date = self.date.trim();
+        let opening = self.opening.trim();
+
+        // move numbers are left out so "1. e4 c5" and "e4 c5" find the same games
+        let opening_moves = opening
+            .split_whitespace()
+            .filter(|t| !t.ends_with('.'))
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        (player.is_empty() || has("White", player) || has("Black", player))
+            && (self.result == 0 || entry.game.tag("Result") == Some(RESULTS[self.result]))
+            && (date.is_empty() || has("Date", date))
+            && (opening.is_empty()
+                || has("ECO", opening)
+                || has("Opening", opening)
+                || entry
+                    .opening
+                    .is_some_and(|o| contains(o.eco, opening) || contains(o.name, opening))
+                || (!opening_moves.is_empty()
+                    && (entry.sans == opening_moves
+                        || entry.sans.starts_with(&format!("{opening_moves} ")))))
+    }
+}
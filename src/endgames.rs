@@ -0,0 +1,163 @@
+// classic endgames to practice against an engine, each with something to achieve
+//
+// the side to move in a drill's position is the one practicing, the other side is the defender
+
+use crate::{app, game::GameResult, rules};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Goal {
+    Mate { within: Option<usize> }, // the practicing side's moves
+    Draw,
+}
+
+pub struct Drill {
+    pub name: &'static str,
+    pub fen: &'static str,
+    pub goal: Goal,
+    pub description: &'static str,
+}
+
+pub static DRILLS: [Drill; 5] = [
+    Drill {
+        name: "King And Queen Vs King",
+        fen: "8/8/8/4k3/8/8/8/3QK3 w - - 0 1",
+        goal: Goal::Mate { within: Some(10) },
+        description: "Mate in 10 moves, push the king to the edge with the queen",
+    },
+    Drill {
+        name: "King And Rook Vs King",
+        fen: "8/8/8/4k3/8/8/8/4K2R w - - 0 1",
+        goal: Goal::Mate { within: Some(16) },
+        description: "Mate in 16 moves, cut the king off with the rook and box it in",
+    },
+    Drill {
+        name: "Lucena Position",
+        fen: "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1",
+        goal: Goal::Mate { within: None },
+        description: "Win it, build a bridge with the rook to shelter your king from checks",
+    },
+    Drill {
+        name: "Philidor Position",
+        fen: "4k3/8/1r6/4PK2/8/8/8/7R b - - 0 1",
+        goal: Goal::Draw,
+        description: "Hold the draw as black, keep the rook on the sixth rank until the pawn \
+                      advances, then check from behind",
+    },
+    Drill {
+        name: "Bishop And Knight Mate",
+        fen: "8/8/8/4k3/8/8/8/4KBN1 w - - 0 1",
+        goal: Goal::Mate { within: Some(33) },
+        description: "Mate in 33 moves, drive the king to a corner the bishop controls",
+    },
+];
+
+impl Goal {
+    /// what a drill game is at given its state without the goal and the game so far,
+    /// `practicing` is the side the goal is for
+    pub fn judge(
+        &self,
+        state: app::State,
+        practicing: usize,
+        history: &rules::History,
+    ) -> app::State {
+        let practicing_wins = if practicing == 0 {
+            GameResult::WhiteWins
+        } else {
+            GameResult::BlackWins
+        };
+
+        match (self, state) {
+            // winning on time or by resignation doesn't show the mate can be done
+            (Goal::Mate { .. }, app::State::GameFinish { message, result })
+                if result == practicing_wins =>
+            {
+                if message.ends_with("By Checkmate") {
+                    app::State::GameFinish {
+                        message: "Drill Passed, Mate Delivered",
+                        result,
+                    }
+                } else {
+                    app::State::GameFinish {
+                        message: "Drill Failed, Won Without Mate",
+                        result: GameResult::Unfinished,
+                    }
+                }
+            }
+            (Goal::Draw, app::State::GameFinish { result, .. }) if result == GameResult::Draw => {
+                app::State::GameFinish {
+                    message: "Drill Passed, Draw Held",
+                    result,
+                }
+            }
+            (Goal::Mate { within: Some(n) }, app::State::InGame) => {
+                // the practicing side moves first
+                let made = history.ply().div_ceil(2);
+
+                if made >= *n && history.current().side() != practicing {
+                    app::State::GameFinish {
+                        message: "Drill Failed, No Mate In Time",
+                        result: GameResult::Unfinished,
+                    }
+                } else {
+                    app::State::InGame
+                }
+            }
+            (_, state) => state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::Variant;
+
+    fn history(fen: &str, moves: &[&str]) -> rules::History {
+        let mut history =
+            rules::History::new(rules::Board::from_fen(fen, Variant::Standard, false).unwrap());
+        for mov in moves {
+            let mov = history.current().parse_move(mov).unwrap();
+            history.push(mov);
+        }
+
+        history
+    }
+
+    fn message(state: app::State) -> Option<&'static str> {
+        match state {
+            app::State::GameFinish { message, .. } => Some(message),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn mate_goals_count_the_practicing_sides_moves() {
+        let goal = Goal::Mate { within: Some(2) };
+        let fen = "8/8/8/4k3/8/8/8/3QK3 w - - 0 1";
+
+        let in_time = history(fen, &["Qd2", "Ke4"]);
+        assert!(message(goal.judge(app::State::InGame, 0, &in_time)).is_none());
+
+        // the second move went by without mate
+        let out_of_time = history(fen, &["Qd2", "Ke4", "Qe2"]);
+        assert_eq!(
+            message(goal.judge(app::State::InGame, 0, &out_of_time)),
+            Some("Drill Failed, No Mate In Time")
+        );
+    }
+
+    #[test]
+    fn wins_without_mate_fail() {
+        let goal = Goal::Mate { within: None };
+        let history = history("8/8/8/4k3/8/8/8/3QK3 w - - 0 1", &[]);
+
+        let resigned = app::State::GameFinish {
+            message: "White Wins By Resignation",
+            result: GameResult::WhiteWins,
+        };
+        assert_eq!(
+            message(goal.judge(resigned, 0, &history)),
+            Some("Drill Failed, Won Without Mate")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// parsing for the lines engines send back while searching, libchess only cares about "bestmove"
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Score {
+    Cp(i32),
+    Mate(i32),
+}
+
+impl Score {
+    /// the score as seen by the other side
+    pub fn negated(&self) -> Self {
+        match self {
+            Score::Cp(cp) => Score::Cp(-cp),
+            Score::Mate(n) => Score::Mate(-n),
+        }
+    }
+
+    pub fn to_display_fmt(self) -> String {
+        match self {
+            Score::Cp(cp) => format!("{:+.2}", cp as f32 / 100.0),
+            Score::Mate(n) => {
+                if n < 0 {
+                    format!("-M{}", n.abs())
+                } else {
+                    format!("M{n}")
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EngineInfo {
+    pub depth: u32,
+    pub multipv: usize,
+    pub score: Option<Score>, // from the point of view of the side to move
+    pub pv: Vec<String>,
+}
+
+pub enum EngineOutput {
+    Info(EngineInfo),
+    BestMove(String),
+    Other,
+}
+
+pub fn parse_line(line: &str) -> EngineOutput {
+    let mut tokens = line.split_whitespace();
+
+    match tokens.next() {
+        Some("bestmove") => match tokens.next() {
+            Some(mov) => EngineOutput::BestMove(mov.to_string()),
+            None => EngineOutput::Other,
+        },
+        Some("info") => {
+            let mut info = EngineInfo {
+                depth: 0,
+                multipv: 1,
+                score: None,
+                pv: Vec::new(),
+            };
+
+            while let Some(token) = tokens.next() {
+                match token {
+                    "depth" => info.depth = tokens.next().and_then(|t| t.parse().ok()).unwrap_or(0),
+                    "multipv" => {
+                        info.multipv = tokens.next().and_then(|t| t.parse().ok()).unwrap_or(1)
+                    }
+                    "score" => {
+                        info.score =
+                            match (tokens.next(), tokens.next().and_then(|t| t.parse().ok())) {
+                                (Some("cp"), Some(cp)) => Some(Score::Cp(cp)),
+                                (Some("mate"), Some(n)) => Some(Score::Mate(n)),
+                                _ => None,
+                            }
+                    }
+                    "pv" => {
+                        info.pv = tokens.by_ref().map(|t| t.to_string()).collect();
+                    }
+                    // "string" eats the rest of the line, so nothing after it can be misread
+                    "string" => break,
+                    _ => {}
+                }
+            }
+
+            // lines like "info currmove e2e4 currmovenumber 1" don't say anything about the search
+            if info.score.is_none() && info.pv.is_empty() {
+                EngineOutput::Other
+            } else {
+                EngineOutput::Info(info)
+            }
+        }
+        _ => EngineOutput::Other,
+    }
+}
+
+/// something an engine says can be set, from the lines it sends before "uciok"
+#[derive(Clone, Debug)]
+pub struct EngineOption {
+    pub name: String,
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+}
+
+/// reads lines like "option name Skill Level type spin default 20 min 0 max 20"
+pub fn parse_option(line: &str) -> Option<EngineOption> {
+    let mut tokens = line.split_whitespace();
+
+    if tokens.next() != Some("option") || tokens.next() != Some("name") {
+        return None;
+    }
+
+    // names can have spaces in them, they run until "type"
+    let name = tokens
+        .by_ref()
+        .take_while(|t| *t != "type")
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let mut option = EngineOption {
+        name,
+        min: None,
+        max: None,
+    };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "min" => option.min = tokens.next().and_then(|t| t.parse().ok()),
+            "max" => option.max = tokens.next().and_then(|t| t.parse().ok()),
+            _ => {}
+        }
+    }
+
+    Some(option)
+}
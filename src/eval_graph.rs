@@ -0,0 +1,87 @@
+// a chart of the evaluation over the course of a game, drawn inside a ui window
+
+use macroquad::{prelude::*, ui};
+
+use crate::{engine_info::Score, pgn};
+
+// evals past this many centipawns are drawn at the edge of the graph
+const MAX_CP: f32 = 800.0;
+
+/// where `score` sits between -1 (black is winning) and 1 (white is winning), mate in 0 has no
+/// sign so `white_moved` says who delivered it
+fn to_height(score: Score, white_moved: bool) -> f32 {
+    match score {
+        Score::Cp(cp) => (cp as f32 / MAX_CP).clamp(-1.0, 1.0),
+        Score::Mate(0) => {
+            if white_moved {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        Score::Mate(n) => n.signum() as f32,
+    }
+}
+
+/// `evals` holds the eval after every move from white's point of view, moves without one keep
+/// the previous eval, returns the ply that was clicked on
+pub fn draw(
+    ui: &mut ui::Ui,
+    size: Vec2,
+    starting_fen: &str,
+    evals: &[Option<Score>],
+    current_ply: usize,
+) -> Option<usize> {
+    let mut canvas = ui.canvas();
+    let xy = canvas.request_space(size);
+    let rect = Rect::new(xy.x, xy.y, size.x, size.y);
+    let mid = rect.y + rect.h / 2.0;
+    let step = rect.w / evals.len().max(1) as f32;
+
+    canvas.rect(rect, GRAY, Color::new(0.15, 0.15, 0.15, 1.0));
+    canvas.line(vec2(rect.x, mid), vec2(rect.x + rect.w, mid), GRAY);
+
+    let mut height = 0.0;
+    let mut last = vec2(rect.x, mid);
+
+    for (idx, eval) in evals.iter().enumerate() {
+        if let Some(score) = eval {
+            height = to_height(*score, pgn::move_number(starting_fen, idx).1);
+        }
+
+        let point = vec2(
+            rect.x + (idx + 1) as f32 * step,
+            mid - height * rect.h / 2.0,
+        );
+
+        // the side that's better gets its color filled in from the middle
+        let fill = if height > 0.0 {
+            WHITE.with_alpha(0.5)
+        } else {
+            BLACK.with_alpha(0.5)
+        };
+        canvas.rect(
+            Rect::new(last.x, point.y.min(mid), step, (point.y - mid).abs()),
+            fill,
+            fill,
+        );
+        canvas.line(last, point, WHITE);
+
+        last = point;
+    }
+
+    let current_x = rect.x + current_ply as f32 * step;
+    canvas.line(
+        vec2(current_x, rect.y),
+        vec2(current_x, rect.y + rect.h),
+        YELLOW,
+    );
+
+    let m = Vec2::from(mouse_position());
+
+    if is_mouse_button_pressed(MouseButton::Left) && rect.contains(m) {
+        Some((((m.x - rect.x) / step).round() as usize).min(evals.len()))
+    } else {
+        None
+    }
+}
@@ -1,20 +1,16 @@
-use libchess::{
-    color as chess_color, moves,
-    piece::{self, bb},
-    pos,
-};
+use libchess::pos;
 
-use crate::{app, visual_board::VisualBoard};
+use crate::{app, board_editor, game, rules, visual_board::VisualBoard};
 
 use macroquad::prelude::*;
 
 pub fn do_board_mouse_events(
     vb: &mut VisualBoard,
-    position: &pos::Position,
+    history: &rules::History,
     game_input_sqs: &mut app::MouseInputSquares,
     engines: [&Option<app::EnginePath>; 2],
 ) {
-    if position.ply() > vb.draw_ply {
+    if history.ply() > vb.draw_ply {
         return; // user is looking at previous positions, so don't do anything
     }
 
@@ -37,56 +33,44 @@ pub fn do_board_mouse_events(
         }
     }
 
+    let board = history.current();
     game_input_sqs.keys_down = get_keys_down();
 
-    if is_mouse_button_pressed(MouseButton::Left) {
-        find_sq_and(vb.pos, vb.is_flipped, vb.square_size, |f, r| {
-            let c = chess_color::of(position.piece_on(pos::make_sq(f, r)));
-            let e = &engines[bb::c_to_idx(c)];
-            if e.is_none() {
-                if game_input_sqs.down_left.is_some() {
-                    vb.mouse_input_sqs.down_left = None;
-                    vb.selected_piece = piece::NONE;
+    // a human waiting on an engine can queue up moves in the meantime
+    let premover = if engines[board.side()].is_some() {
+        (0..2).find(|idx| engines[*idx].is_none())
+    } else {
+        None
+    };
 
-                    game_input_sqs.up_left = Some(pos::make_sq(f, r));
-                } else {
-                    vb.mouse_input_sqs.down_left = Some(pos::make_sq(f, r));
-                    vb.selected_piece = position.piece_on(vb.mouse_input_sqs.down_left.unwrap());
-
-                    game_input_sqs.down_left = Some(pos::make_sq(f, r));
-                    game_input_sqs.up_left = None;
-                }
-            }
-
-            vb.selected_squares.clear();
-            vb.arrows.clear();
-        });
+    // a premove that was still being picked when the turn came around is dropped
+    if premover.is_none() && vb.selected_piece.is_none() {
+        vb.mouse_input_sqs.down_left = None;
     }
 
-    if is_mouse_button_released(MouseButton::Left) {
-        find_sq_and(vb.pos, vb.is_flipped, vb.square_size, |f, r| {
-            if !game_input_sqs
-                .down_left
-                .is_some_and(|sq| sq == pos::make_sq(f, r))
-            {
-                vb.mouse_input_sqs.down_left = None;
-                vb.selected_piece = piece::NONE;
-
-                game_input_sqs.up_left = Some(pos::make_sq(f, r));
-            }
-        });
+    if let Some(human) = premover {
+        do_premove_mouse_events(vb, board, human);
+    } else if engines[board.side()].is_none() {
+        do_move_mouse_events(vb, board, game_input_sqs);
     }
 
     if is_mouse_button_pressed(MouseButton::Right) {
+        if vb.square_at(Vec2::from(mouse_position())).is_some() {
+            vb.premoves.clear();
+        }
+
         find_sq_and(vb.pos, vb.is_flipped, vb.square_size, |f, r| {
-            vb.mouse_input_sqs.down_right = Some(pos::make_sq(f, r));
+            vb.mouse_input_sqs.down_right = Some(rules::square(f as usize, r as usize));
         });
     }
 
     if is_mouse_button_released(MouseButton::Right) {
         find_sq_and(vb.pos, vb.is_flipped, vb.square_size, |tf, tr| {
             let sq = vb.mouse_input_sqs.down_right.unwrap();
-            let (ff, fr) = pos::make_tuple(sq);
+            let (ff, fr) = (
+                rules::file_of(sq) as pos::File,
+                rules::rank_of(sq) as pos::Rank,
+            );
 
             if (ff, fr) != (tf, tr) && !vb.arrows.contains(&(ff, fr, tf, tr)) {
                 vb.arrows.insert((ff, fr, tf, tr));
@@ -103,32 +87,266 @@ pub fn do_board_mouse_events(
     }
 }
 
-pub fn do_key_events(vb: &mut VisualBoard, position: &pos::Position) {
+/// the square under the mouse
+fn hovered_square(vb: &VisualBoard) -> Option<rules::Square> {
+    vb.square_at(Vec2::from(mouse_position()))
+        .map(|(f, r)| rules::square(f as usize, r as usize))
+}
+
+/// a piece can be moved by dragging it or by clicking it and then where it should go, clicking
+/// another piece of the same side selects that one instead, and clicking the selected piece again
+/// lets go of it, the king castles by going onto its own rook when that's legal. pieces in a
+/// Crazyhouse pocket are dragged or clicked onto the board the same way
+fn do_move_mouse_events(
+    vb: &mut VisualBoard,
+    board: &rules::Board,
+    game_input_sqs: &mut app::MouseInputSquares,
+) {
+    let sq = hovered_square(vb);
+    let piece = sq.and_then(|sq| board.squares[sq]);
+    let is_own = piece.is_some_and(|p| p / 6 == board.side());
+    let selected = vb
+        .mouse_input_sqs
+        .down_left
+        .filter(|_| vb.selected_piece.is_some());
+    let castles = |king, rook| {
+        board
+            .legal_moves()
+            .contains(&rules::Move::Castle { king, rook })
+    };
+
+    // the piece under the mouse in the mover's pocket, if they have any of it
+    let pocketed = if board.has_pockets() {
+        let m = Vec2::from(mouse_position());
+
+        vb.pocket_slots()
+            .into_iter()
+            .find(|(piece, rect)| {
+                rect.contains(m)
+                    && piece / 6 == board.side()
+                    && board.pockets[piece / 6][piece % 6] > 0
+            })
+            .map(|(piece, _)| piece)
+    } else {
+        None
+    };
+
+    let submit = |vb: &mut VisualBoard, game_input_sqs: &mut app::MouseInputSquares, from, to| {
+        game_input_sqs.down_left = Some(from);
+        game_input_sqs.up_left = Some(to);
+        vb.mouse_input_sqs.down_left = None;
+        vb.selected_piece = None;
+    };
+    let submit_drop =
+        |vb: &mut VisualBoard, game_input_sqs: &mut app::MouseInputSquares, kind, to| {
+            game_input_sqs.held_drop = Some(kind);
+            game_input_sqs.up_left = Some(to);
+            vb.mouse_input_sqs.held_drop = None;
+            vb.selected_piece = None;
+        };
+
+    if is_mouse_button_pressed(MouseButton::Left) {
+        vb.click_deselects = false;
+        let held_drop = vb.mouse_input_sqs.held_drop.take();
+
+        if let Some(pocketed) = pocketed {
+            vb.click_deselects = held_drop == Some(pocketed % 6);
+            vb.mouse_input_sqs.held_drop = Some(pocketed % 6);
+            vb.mouse_input_sqs.down_left = None;
+            vb.selected_piece = Some(pocketed);
+        } else if let Some(kind) = held_drop {
+            match sq {
+                Some(to) if is_own => {
+                    vb.mouse_input_sqs.down_left = Some(to);
+                    vb.selected_piece = piece;
+                }
+                Some(to) => submit_drop(vb, game_input_sqs, kind, to),
+                // clicked off the board, the piece stays held
+                None => vb.mouse_input_sqs.held_drop = Some(kind),
+            }
+        } else {
+            match (selected, sq) {
+                (Some(from), Some(to)) if from == to => vb.click_deselects = true,
+                (Some(from), Some(to)) if castles(from, to) => submit(vb, game_input_sqs, from, to),
+                (_, Some(to)) if is_own => {
+                    vb.mouse_input_sqs.down_left = Some(to);
+                    vb.selected_piece = piece;
+                }
+                (Some(from), Some(to)) => submit(vb, game_input_sqs, from, to),
+                _ => {
+                    vb.mouse_input_sqs.down_left = None;
+                    vb.selected_piece = None;
+                }
+            }
+        }
+
+        vb.selected_squares.clear();
+        vb.arrows.clear();
+    }
+
+    if is_mouse_button_released(MouseButton::Left) {
+        match (vb.mouse_input_sqs.held_drop, selected, sq) {
+            // dragged out of the pocket
+            (Some(kind), _, Some(to)) => submit_drop(vb, game_input_sqs, kind, to),
+            // the pocket piece clicked again is let go of
+            (Some(_), _, None) if vb.click_deselects => {
+                vb.mouse_input_sqs.held_drop = None;
+                vb.selected_piece = None;
+            }
+            // dropped after a drag
+            (None, Some(from), Some(to)) if from != to => submit(vb, game_input_sqs, from, to),
+            // the selected piece clicked again is let go of
+            (None, Some(_), Some(_)) if vb.click_deselects => {
+                vb.mouse_input_sqs.down_left = None;
+                vb.selected_piece = None;
+            }
+            // dropped off the board, the piece stays selected
+            _ => {}
+        }
+    }
+}
+
+/// `human` is the index of the side whose premoves these are
+fn do_premove_mouse_events(vb: &mut VisualBoard, board: &rules::Board, human: usize) {
+    let sq = hovered_square(vb);
+
+    // squares the human's earlier premoves will have put a piece on count as theirs too
+    let is_own = sq.is_some_and(|sq| {
+        board.squares[sq].is_some_and(|p| p / 6 == human)
+            || vb.premoves.iter().any(|(_, to, _)| *to == sq)
+    });
+
+    // pawns premoved to the last rank promote to whatever was held last, a queen until then
+    if let Some(kind) = game::held_promotion(&get_keys_down()) {
+        vb.premove_promotion = kind;
+    }
+
+    if is_mouse_button_pressed(MouseButton::Left) {
+        match (vb.mouse_input_sqs.down_left, sq) {
+            (Some(from), Some(to)) if from != to => {
+                vb.premoves.push((from, to, vb.premove_promotion));
+                vb.mouse_input_sqs.down_left = None;
+            }
+            (None, Some(sq)) if is_own => vb.mouse_input_sqs.down_left = Some(sq),
+            _ => vb.mouse_input_sqs.down_left = None,
+        }
+
+        vb.selected_squares.clear();
+        vb.arrows.clear();
+    }
+
+    // dropping a dragged piece on another square queues the move straight away
+    if is_mouse_button_released(MouseButton::Left)
+        && let (Some(from), Some(to)) = (vb.mouse_input_sqs.down_left, sq)
+        && from != to
+    {
+        vb.premoves.push((from, to, vb.premove_promotion));
+        vb.mouse_input_sqs.down_left = None;
+    }
+}
+
+/// takes the next queued premove once it's the human's turn, the whole queue is dropped if it
+/// turns out to be illegal
+pub fn take_premove(
+    vb: &mut VisualBoard,
+    history: &rules::History,
+) -> Option<app::MouseInputSquares> {
+    if vb.premoves.is_empty() || history.ply() > vb.draw_ply {
+        return None;
+    }
+
+    let (from, to, promotion) = vb.premoves.remove(0);
+
+    match history.current().move_between(from, to, Some(promotion)) {
+        Some(mov) => Some(input_squares_for(mov)),
+        None => {
+            vb.premoves.clear();
+            None
+        }
+    }
+}
+
+/// the squares and keys that make `game::player_move` play `mov`, castling is the king going onto
+/// its own rook
+pub fn input_squares_for(mov: rules::Move) -> app::MouseInputSquares {
+    let (from, to, promotion, held_drop) = match mov {
+        rules::Move::Normal {
+            from,
+            to,
+            promotion,
+        } => (Some(from), to, promotion, None),
+        rules::Move::Castle { king, rook } => (Some(king), rook, None, None),
+        rules::Move::Drop { kind, to } => (None, to, None, Some(kind)),
+    };
+
+    let keys_down = match promotion {
+        Some(rules::QUEEN) => [KeyCode::Q].into_iter().collect(),
+        Some(rules::ROOK) => [KeyCode::R].into_iter().collect(),
+        Some(rules::BISHOP) => [KeyCode::B].into_iter().collect(),
+        Some(rules::KNIGHT) => [KeyCode::N].into_iter().collect(),
+        Some(rules::KING) => [KeyCode::K].into_iter().collect(),
+        _ => Default::default(),
+    };
+
+    app::MouseInputSquares {
+        keys_down,
+        up_left: Some(to),
+        down_left: from,
+        down_right: None,
+        held_drop,
+    }
+}
+
+pub fn do_key_events(vb: &mut VisualBoard, history: &rules::History) {
     if is_key_pressed(KeyCode::Left) {
         if vb.draw_ply > 0 {
-            let mov = position.moves_opt()[vb.draw_ply].unwrap();
-            vb.make_move(
-                moves::Move::new(mov.to_sq(), mov.from_sq(), mov.type_of()),
-                position.side_to_move(),
-            );
+            vb.unmake_move(history.moves[vb.draw_ply - 1]);
             vb.draw_ply -= 1;
         }
     } else if is_key_pressed(KeyCode::Right) {
-        if vb.draw_ply < position.ply() {
+        if vb.draw_ply < history.ply() {
+            vb.make_move(&history.boards[vb.draw_ply], history.moves[vb.draw_ply]);
             vb.draw_ply += 1;
-            let mov = position.moves_opt()[vb.draw_ply].unwrap();
-            vb.make_move(mov, position.side_to_move());
         }
     } else if is_key_pressed(KeyCode::Up) {
-        vb.draw_ply = position.ply();
-        vb.sync_pieces(position);
+        vb.draw_ply = history.ply();
+        vb.sync_pieces(history);
     } else if is_key_pressed(KeyCode::Down) {
         vb.draw_ply = 0;
-        vb.sync_pieces(&pos::Position::from_fen(
-            pos::START_FEN,
-            &libchess::init().zb, // forgive me
-        ));
+        vb.sync_pieces(history);
     } else if is_key_pressed(KeyCode::F) {
         vb.is_flipped = !vb.is_flipped;
     }
 }
+
+/// `palette` holds the piece index and screen area of every piece that can be dragged onto the board
+pub fn do_editor_mouse_events(
+    vb: &VisualBoard,
+    editor: &mut board_editor::Editor,
+    palette: &[(usize, Rect)],
+) {
+    let m = Vec2::from(mouse_position());
+
+    if is_mouse_button_pressed(MouseButton::Left) {
+        if let Some((piece, _)) = palette.iter().find(|(_, rect)| rect.contains(m)) {
+            editor.held_piece = Some(*piece);
+        } else if let Some((f, r)) = vb.square_at(m) {
+            editor.held_piece = editor.setup.piece_on(f, r);
+            editor.setup.set_piece(f, r, None);
+        }
+    }
+
+    // pieces dropped outside of the board are removed
+    if is_mouse_button_released(MouseButton::Left)
+        && let Some(piece) = editor.held_piece.take()
+        && let Some((f, r)) = vb.square_at(m)
+    {
+        editor.setup.set_piece(f, r, Some(piece));
+    }
+
+    if is_mouse_button_pressed(MouseButton::Right)
+        && let Some((f, r)) = vb.square_at(m)
+    {
+        editor.setup.set_piece(f, r, None);
+    }
+}
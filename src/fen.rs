@@ -0,0 +1,212 @@
+use std::fmt;
+
+use crate::{
+    board_editor::{self, SetupPosition},
+    rules,
+    variant::Variant,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FenField {
+    Placement,
+    SideToMove,
+    Castling,
+    EnPassant,
+    HalfmoveClock,
+    FullmoveNumber,
+    Position, // problems with the position as a whole, like the side not to move being in check
+}
+
+impl fmt::Display for FenField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FenField::Placement => "Piece Placement",
+                FenField::SideToMove => "Side To Move",
+                FenField::Castling => "Castling Rights",
+                FenField::EnPassant => "En Passant Square",
+                FenField::HalfmoveClock => "Halfmove Clock",
+                FenField::FullmoveNumber => "Fullmove Number",
+                FenField::Position => "Position",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FenError {
+    pub field: FenField,
+    pub reason: String,
+}
+
+impl FenError {
+    pub fn new(field: FenField, reason: impl Into<String>) -> Self {
+        Self {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {}: {}", self.field, self.reason)
+    }
+}
+
+/// reads every field of `fen` without checking if the position itself makes sense, the move
+/// counters can be left out
+pub fn parse(fen: &str) -> Result<SetupPosition, FenError> {
+    let fields = fen.split_whitespace().collect::<Vec<&str>>();
+    let mut setup = SetupPosition::empty();
+
+    if fields.len() > 6 {
+        return Err(FenError::new(
+            FenField::Position,
+            format!("Expected At Most 6 Fields, Found {}", fields.len()),
+        ));
+    }
+
+    let placement = fields
+        .first()
+        .ok_or_else(|| FenError::new(FenField::Placement, "Missing"))?;
+    let ranks = placement.split('/').collect::<Vec<&str>>();
+
+    if ranks.len() != 8 {
+        return Err(FenError::new(
+            FenField::Placement,
+            format!("Expected 8 Ranks, Found {}", ranks.len()),
+        ));
+    }
+
+    for (idx, rank) in ranks.iter().enumerate() {
+        let r = 7 - idx;
+        let mut f = 0;
+
+        for c in rank.chars() {
+            if let Some(skip) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                f += skip as usize;
+            } else {
+                let piece = board_editor::PIECE_CHARS
+                    .iter()
+                    .position(|p| *p == c)
+                    .ok_or_else(|| {
+                        FenError::new(
+                            FenField::Placement,
+                            format!("Unknown Piece '{c}' On Rank {}", r + 1),
+                        )
+                    })?;
+
+                if f < 8 {
+                    setup.board[r][f] = Some(piece);
+                }
+                f += 1;
+            }
+        }
+
+        if f != 8 {
+            return Err(FenError::new(
+                FenField::Placement,
+                format!("Rank {} Has {f} Squares Instead Of 8", r + 1),
+            ));
+        }
+    }
+
+    setup.white_to_move = match fields.get(1) {
+        Some(&"w") => true,
+        Some(&"b") => false,
+        Some(s) => {
+            return Err(FenError::new(
+                FenField::SideToMove,
+                format!("Expected 'w' Or 'b', Found '{s}'"),
+            ));
+        }
+        None => return Err(FenError::new(FenField::SideToMove, "Missing")),
+    };
+
+    match fields.get(2) {
+        Some(&"-") => {}
+        Some(castling) => {
+            for c in castling.chars() {
+                let idx = match c {
+                    'K' | 'Q' | 'k' | 'q' => {
+                        ['K', 'Q', 'k', 'q'].iter().position(|r| *r == c).unwrap()
+                    }
+                    // shredder-fen names the rook's file instead, which side it's on is what counts
+                    'A'..='H' | 'a'..='h' => {
+                        let white = c.is_ascii_uppercase();
+                        let (rank, king) = if white { (0, 5) } else { (7, 11) };
+                        let king_f = (0..8)
+                            .find(|f| setup.board[rank][*f] == Some(king))
+                            .ok_or_else(|| {
+                                FenError::new(
+                                    FenField::Castling,
+                                    format!("'{c}' Needs The King On Its First Rank"),
+                                )
+                            })?;
+                        let kingside = (c.to_ascii_lowercase() as u8 - b'a') as usize > king_f;
+
+                        match (white, kingside) {
+                            (true, true) => 0,
+                            (true, false) => 1,
+                            (false, true) => 2,
+                            (false, false) => 3,
+                        }
+                    }
+                    _ => {
+                        return Err(FenError::new(
+                            FenField::Castling,
+                            format!("Unknown Castling Right '{c}'"),
+                        ));
+                    }
+                };
+
+                if setup.castling[idx] {
+                    return Err(FenError::new(
+                        FenField::Castling,
+                        format!("'{c}' Appears More Than Once"),
+                    ));
+                }
+
+                setup.castling[idx] = true;
+            }
+        }
+        None => return Err(FenError::new(FenField::Castling, "Missing")),
+    }
+
+    setup.en_passant = match fields.get(3) {
+        Some(&"-") => None,
+        Some(sq) => Some(board_editor::parse_square(sq).ok_or_else(|| {
+            FenError::new(FenField::EnPassant, format!("'{sq}' Is Not A Square"))
+        })?),
+        None => return Err(FenError::new(FenField::EnPassant, "Missing")),
+    };
+
+    if let Some(n) = fields.get(4) {
+        setup.halfmoves = n.parse().map_err(|_| {
+            FenError::new(
+                FenField::HalfmoveClock,
+                format!("'{n}' Is Not A Non-Negative Number"),
+            )
+        })?;
+    }
+
+    if let Some(n) = fields.get(5) {
+        setup.fullmoves = n.parse().map_err(|_| {
+            FenError::new(
+                FenField::FullmoveNumber,
+                format!("'{n}' Is Not A Non-Negative Number"),
+            )
+        })?;
+    }
+
+    Ok(setup)
+}
+
+/// parses and validates `fen`, returning it with all six fields and the castling rights written
+/// the way `rules` writes them
+pub fn normalize(fen: &str, variant: Variant, chess960: bool) -> Result<String, FenError> {
+    rules::Board::from_fen(fen, variant, chess960).map(|board| board.to_fen())
+}
@@ -3,14 +3,18 @@ use std::{collections::HashSet, io, time};
 
 // use macroquad::prelude::*;
 
-use libchess::{
-    ZobristValues, color as chess_color, moves,
-    piece::{self, bb},
-    pos, uci,
-};
+use libchess::{color as chess_color, piece::bb, uci};
 use macroquad::input::KeyCode;
 
-use crate::app;
+use crate::{
+    app, endgames,
+    engine_info::{self, EngineOption, Score},
+    rules, strength,
+    variant::Variant,
+};
+
+// an engine takes a draw offer if it doesn't think it's better than this
+const ENGINE_DRAW_ACCEPT_CP: i32 = 0;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum EngineInitPhase {
@@ -22,7 +26,7 @@ pub enum EngineInitPhase {
 }
 
 impl EngineInitPhase {
-    fn cycle(&mut self) {
+    pub fn cycle(&mut self) {
         *self = match self {
             EngineInitPhase::SendUci => EngineInitPhase::WaitUciOk,
             EngineInitPhase::WaitUciOk => EngineInitPhase::SendNewGame,
@@ -33,6 +37,36 @@ impl EngineInitPhase {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unfinished, // cut short without a result, like a failed drill
+}
+
+impl GameResult {
+    pub fn to_pgn_fmt(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*",
+        }
+    }
+}
+
+/// things the players can do besides moving, requested from the ui thread
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Takeback,
+    Resign(chess_color::Color),
+    OfferDraw(chess_color::Color),
+    AcceptDraw(chess_color::Color),
+    DeclineDraw(chess_color::Color),
+    ClaimDraw,
+}
+
 #[derive(Default, Clone)]
 pub struct Settings {
     pub position_fen: String,
@@ -43,22 +77,57 @@ pub struct Settings {
     pub btime_s: String,
     pub wincrement_ms: String,
     pub bincrement_ms: String,
+    pub review_engine_path: Option<app::EnginePath>,
+    pub review_movetime_ms: String,
+    pub chess960: bool,
+    pub variant: Variant,
+    pub white_engine_elo: String, // empty for full strength
+    pub black_engine_elo: String,
+    pub goal: Option<endgames::Goal>, // for endgame drills
+}
+
+/// what's known about a move besides the move itself
+#[derive(Clone, Copy)]
+pub struct MoveRecord {
+    pub clock: time::Duration, // what the mover had left after the move, increment included
+    pub think_time: time::Duration,
+    pub depth: Option<u32>,
+    pub score: Option<Score>, // from white's point of view
 }
 
 #[derive(Clone)]
 pub struct PostRunInfo {
-    pub position: pos::Position,
+    pub history: rules::History,
     pub app_state: app::State,
+    pub draw_offered_by: Option<chess_color::Color>,
+    pub notice: Option<&'static str>,
+    pub records: Vec<MoveRecord>,
 }
 
 pub struct Game {
-    sarting_fen: String,
-    pub position: pos::Position,
+    pub history: rules::History,
+    goal: Option<endgames::Goal>,
+    practicing: usize, // the side the goal is for, whoever moves first
     pub engines: [Option<uci::Engine>; 2],
     pub engine_init_phases: [EngineInitPhase; 2],
+    engine_options: [Vec<EngineOption>; 2],
+    engine_elos: [Option<u32>; 2],
+    weakened_by_us: [bool; 2], // engines that can't limit their own strength
+    pv_lines: [Vec<Option<(Score, String)>>; 2], // of the current search, by multipv
     pub engine_move_requested: bool,
+    pub paused: bool,
+    discard_next_engine_move: bool,
+    pub last_engine_scores: [Option<Score>; 2], // of the current search only
+    last_engine_depths: [Option<u32>; 2],       // of the current search only
+    draw_offered_by: Option<chess_color::Color>,
+    notice: Option<&'static str>,
+    finish: Option<app::State>, // set when the game ends by something other than a move
     pub wtime: time::Duration,
     pub btime: time::Duration,
+    starting_clocks: (time::Duration, time::Duration),
+    increments: [time::Duration; 2],
+    clock_history: Vec<(time::Duration, time::Duration)>, // clocks at the end of every ply
+    pub move_records: Vec<MoveRecord>,
     pub max_engine_think_time: time::Duration,
     pub elapsed_engine_think_time: time::Duration,
     pub ui_thread_delta_time: time::Duration,
@@ -66,10 +135,18 @@ pub struct Game {
 }
 
 impl Game {
-    pub fn new(options: &Settings, zb: &ZobristValues) -> io::Result<Self> {
+    pub fn new(options: &Settings) -> io::Result<Self> {
+        let wtime = time::Duration::from_secs(options.wtime_s.parse().unwrap());
+        let btime = time::Duration::from_secs(options.btime_s.parse().unwrap());
+
+        let board =
+            rules::Board::from_fen(&options.position_fen, options.variant, options.chess960)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+
         Ok(Self {
-            sarting_fen: options.position_fen.clone(),
-            position: pos::Position::from_fen(&options.position_fen, zb),
+            goal: options.goal,
+            practicing: board.side(),
+            history: rules::History::new(board),
             engines: [
                 match &options.white_engine_path {
                     Some(e) => Some(uci::Engine::new(&e.path)?),
@@ -81,28 +158,49 @@ impl Game {
                 },
             ],
             engine_init_phases: [EngineInitPhase::SendUci; 2],
+            engine_options: [Vec::new(), Vec::new()],
+            engine_elos: [
+                options.white_engine_elo.parse().ok(),
+                options.black_engine_elo.parse().ok(),
+            ],
+            weakened_by_us: [false; 2],
+            pv_lines: [Vec::new(), Vec::new()],
             mouse_input_sqs: app::MouseInputSquares {
                 keys_down: HashSet::new(),
                 down_left: None,
                 up_left: None,
                 down_right: None,
+                held_drop: None,
             },
-            wtime: time::Duration::from_secs(options.wtime_s.parse().unwrap()),
-            btime: time::Duration::from_secs(options.btime_s.parse().unwrap()),
+            wtime,
+            btime,
+            starting_clocks: (wtime, btime),
+            increments: [
+                time::Duration::from_millis(options.wincrement_ms.parse().unwrap()),
+                time::Duration::from_millis(options.bincrement_ms.parse().unwrap()),
+            ],
+            clock_history: Vec::new(),
+            move_records: Vec::new(),
             max_engine_think_time: time::Duration::from_secs(
                 options.max_engine_think_time_s.parse().unwrap(),
             ),
             ui_thread_delta_time: time::Duration::ZERO,
             elapsed_engine_think_time: time::Duration::ZERO,
             engine_move_requested: false,
+            paused: false,
+            discard_next_engine_move: false,
+            last_engine_scores: [None; 2],
+            last_engine_depths: [None; 2],
+            draw_offered_by: None,
+            notice: None,
+            finish: None,
         })
     }
 }
 
 impl Game {
     pub fn init_engines(&mut self) -> io::Result<()> {
-        let mut idx = 0;
-        for engine in &mut self.engines {
+        for (idx, engine) in self.engines.iter_mut().enumerate() {
             if let Some(e) = engine {
                 match self.engine_init_phases[idx] {
                     EngineInitPhase::SendUci => {
@@ -110,166 +208,372 @@ impl Game {
                         self.engine_init_phases[idx].cycle();
                     }
                     EngineInitPhase::WaitUciOk => {
-                        if e.try_get(uci::UCI_OK).is_some() {
-                            self.engine_init_phases[idx].cycle();
+                        // the options come before "uciok", they're needed to limit strength
+                        while let Some(line) = e.try_get("") {
+                            if let Some(option) = engine_info::parse_option(&line) {
+                                self.engine_options[idx].push(option);
+                            } else if line.trim() == uci::UCI_OK {
+                                self.engine_init_phases[idx].cycle();
+                                break;
+                            }
                         }
                     }
                     EngineInitPhase::SendNewGame => {
+                        let board = &self.history.boards[0];
+                        if board.chess960 {
+                            e.send("setoption name UCI_Chess960 value true")?;
+                        }
+                        if let Some(name) = board.variant.to_uci_fmt() {
+                            e.send(&format!("setoption name UCI_Variant value {name}"))?;
+                        }
+                        if let Some(elo) = self.engine_elos[idx] {
+                            let (commands, weaken) =
+                                strength::setup_commands(&self.engine_options[idx], elo);
+                            for command in commands {
+                                e.send(&command)?;
+                            }
+                            self.weakened_by_us[idx] = weaken;
+                        }
                         e.send(uci::NEW_GAME)?;
                         e.send(uci::IS_READY)?;
                         self.engine_init_phases[idx].cycle();
                     }
-                    EngineInitPhase::WaitIsReady => {
-                        if e.try_get(uci::READY_OK).is_some() {
-                            self.engine_init_phases[idx].cycle();
-                        }
+                    EngineInitPhase::WaitIsReady if e.try_get(uci::READY_OK).is_some() => {
+                        self.engine_init_phases[idx].cycle();
                     }
                     _ => {}
                 }
             }
-
-            idx += 1;
         }
 
         Ok(())
     }
 
-    fn make_move(&mut self, mov: moves::Move, zb: &ZobristValues) {
-        self.position.make_move(mov, zb);
+    pub fn side_to_move(&self) -> chess_color::Color {
+        self.history.current().side_to_move()
     }
 
-    pub fn run(&mut self, lc_data: &libchess::InitData) -> (PostRunInfo, bool) {
-        let legal_moves = moves::gen_legal(&mut self.position, &lc_data);
+    fn make_move(&mut self, mov: rules::Move) {
+        // an offer stands until the side it was made to has moved
+        if self
+            .draw_offered_by
+            .is_some_and(|c| c != self.side_to_move())
+        {
+            self.draw_offered_by = None;
+        }
 
-        let mut post_run_info = PostRunInfo {
-            position: pos::Position::blank(),
-            app_state: app::State::InGame,
+        self.notice = None;
+
+        // the mover's clock only ran since the opponent's last move
+        let mover_idx = self.history.current().side();
+        let turn_start = self
+            .clock_history
+            .last()
+            .copied()
+            .unwrap_or(self.starting_clocks);
+        let (turn_start, clock) = if mover_idx == 0 {
+            (turn_start.0, self.wtime)
+        } else {
+            (turn_start.1, self.btime)
         };
 
-        if legal_moves.len() == 0 {
-            if self.position.is_check(&lc_data.masks) {
-                post_run_info.app_state = app::State::GameFinish {
-                    message: match self.position.side_to_move() {
-                        chess_color::WHITE => "Black Wins By Checkmate",
-                        chess_color::BLACK => "White Wins By Checkmate",
-                        _ => panic!(),
-                    },
-                };
-            } else {
-                post_run_info.app_state = app::State::GameFinish {
-                    message: "Draw By Stalemate",
+        // an engine's score for the position is also its score for the move it picked
+        let is_engine = self.engines[mover_idx].is_some();
+        self.move_records.push(MoveRecord {
+            clock: clock + self.increments[mover_idx],
+            think_time: turn_start.saturating_sub(clock),
+            depth: self.last_engine_depths[mover_idx].filter(|_| is_engine),
+            score: self.last_engine_scores[mover_idx]
+                .filter(|_| is_engine)
+                .map(|s| if mover_idx == 0 { s } else { s.negated() }),
+        });
+
+        self.clock_history.push((self.wtime, self.btime));
+        self.history.push(mov);
+    }
+
+    /// returns the clocks to go back to if the action rewound the game
+    pub fn do_action(&mut self, action: Action) -> Option<(time::Duration, time::Duration)> {
+        match action {
+            Action::Takeback => return self.takeback(),
+            Action::Resign(c) => {
+                self.finish = Some(if c == chess_color::WHITE {
+                    app::State::GameFinish {
+                        message: "Black Wins By Resignation",
+                        result: GameResult::BlackWins,
+                    }
+                } else {
+                    app::State::GameFinish {
+                        message: "White Wins By Resignation",
+                        result: GameResult::WhiteWins,
+                    }
+                });
+            }
+            Action::OfferDraw(c) => {
+                let opponent_idx = 1 - bb::c_to_idx(c);
+
+                // engines decide on their own evaluation, from their last search
+                let engine_accepts = match self.last_engine_scores[opponent_idx] {
+                    Some(Score::Cp(cp)) => cp <= ENGINE_DRAW_ACCEPT_CP,
+                    Some(Score::Mate(n)) => n < 0,
+                    None => false,
                 };
+
+                if self.engines[opponent_idx].is_none() {
+                    self.draw_offered_by = Some(c);
+                    self.notice = Some("Draw Offered");
+                } else if engine_accepts {
+                    self.finish = Some(app::State::GameFinish {
+                        message: "Draw By Agreement",
+                        result: GameResult::Draw,
+                    });
+                } else {
+                    self.notice = Some("Draw Offer Declined");
+                }
+            }
+            // only the side the offer was made to can answer it
+            Action::AcceptDraw(c) => {
+                if self.draw_offered_by.is_some_and(|by| by != c) {
+                    self.finish = Some(app::State::GameFinish {
+                        message: "Draw By Agreement",
+                        result: GameResult::Draw,
+                    });
+                }
+            }
+            Action::DeclineDraw(c) => {
+                if self.draw_offered_by.is_some_and(|by| by != c) {
+                    self.draw_offered_by = None;
+                    self.notice = Some("Draw Offer Declined");
+                }
+            }
+            Action::ClaimDraw => {
+                if let Some(message) = self.claimable_draw() {
+                    self.finish = Some(app::State::GameFinish {
+                        message,
+                        result: GameResult::Draw,
+                    });
+                } else {
+                    self.notice = Some("No Draw To Claim");
+                }
             }
         }
 
-        if self.position.is_3_rep() {
-            post_run_info.app_state = app::State::GameFinish {
-                message: "Draw By Three-Fold Repetition",
-            };
-        }
+        None
+    }
 
-        if self.position.rule50() == pos::RULE_50_PLIES {
-            post_run_info.app_state = app::State::GameFinish {
-                message: "Draw By Fifty Move Rule",
-            };
+    /// the side to move can claim a draw if the position is already drawn, or if one of its
+    /// moves would make it so
+    fn claimable_draw(&self) -> Option<&'static str> {
+        let mut candidates = vec![self.history.clone()];
+
+        for mov in self.history.current().legal_moves() {
+            let mut after = self.history.clone();
+            after.push(mov);
+            candidates.push(after);
         }
 
-        if self.position.insufficient_material(chess_color::WHITE)
-            && self.position.insufficient_material(chess_color::BLACK)
+        if candidates.iter().any(|h| h.is_3_rep()) {
+            Some("Draw By Claimed Three-Fold Repetition")
+        } else if candidates
+            .iter()
+            .any(|h| h.current().halfmoves >= rules::FIFTY_MOVE_PLIES)
         {
-            post_run_info.app_state = app::State::GameFinish {
-                message: "Draw By Insufficient Material",
-            };
+            Some("Draw By Claimed Fifty Move Rule")
+        } else {
+            None
         }
+    }
+
+    /// unmakes the last move, or the last two when the last one was played by an engine so that
+    /// it's a human's turn again, returns the clocks as they were at the restored ply
+    pub fn takeback(&mut self) -> Option<(time::Duration, time::Duration)> {
+        if self.engines[0].is_some() && self.engines[1].is_some() {
+            return None;
+        }
+
+        let to_move_idx = self.history.current().side();
+        let plies = if self.engines[1 - to_move_idx].is_some() {
+            2
+        } else {
+            1
+        };
+
+        let target_ply = self.history.ply().checked_sub(plies)?;
+
+        self.stop_engine_search();
+
+        self.history.truncate(target_ply);
+
+        // the clocks as the ply before the target ended, the starting ones if there wasn't one
+        let (wtime, btime) = target_ply
+            .checked_sub(1)
+            .map_or(self.starting_clocks, |i| self.clock_history[i]);
+        self.clock_history.truncate(target_ply);
+        self.move_records.truncate(target_ply);
+        self.wtime = wtime;
+        self.btime = btime;
+
+        Some((wtime, btime))
+    }
+
+    pub fn run(&mut self) -> (PostRunInfo, bool) {
+        let mut post_run_info = PostRunInfo {
+            history: rules::History::new(rules::Board::start()),
+            app_state: app::State::InGame,
+            draw_offered_by: self.draw_offered_by,
+            notice: self.notice,
+            records: Vec::new(),
+        };
+
+        if let Some((message, result)) = self.history.ending() {
+            post_run_info.app_state = app::State::GameFinish { message, result };
+        }
+
+        // flagging only loses if the opponent could still deliver mate by some legal sequence, in
+        // variants where mating is how games are won
+        let board = self.history.current();
+        let flag_draws = |opponent| {
+            board.variant.insufficient_material_draws() && board.insufficient_material(opponent)
+        };
 
         if self.wtime.is_zero() {
-            post_run_info.app_state = app::State::GameFinish {
-                message: "Black Wins On Time",
+            post_run_info.app_state = if flag_draws(1) {
+                app::State::GameFinish {
+                    message: "Draw By Timeout Vs Insufficient Material",
+                    result: GameResult::Draw,
+                }
+            } else {
+                app::State::GameFinish {
+                    message: "Black Wins On Time",
+                    result: GameResult::BlackWins,
+                }
             };
         }
 
         if self.btime.is_zero() {
-            post_run_info.app_state = app::State::GameFinish {
-                message: "White Wins On Time",
+            post_run_info.app_state = if flag_draws(0) {
+                app::State::GameFinish {
+                    message: "Draw By Timeout Vs Insufficient Material",
+                    result: GameResult::Draw,
+                }
+            } else {
+                app::State::GameFinish {
+                    message: "White Wins On Time",
+                    result: GameResult::WhiteWins,
+                }
             };
         }
 
-        if let Some(mov) = self.get_move() {
-            self.engine_move_requested = false;
+        if let Some(finish) = &self.finish {
+            post_run_info.app_state = finish.clone();
+        }
 
-            if legal_moves.contains(&mov) {
-                self.make_move(mov, &lc_data.zb);
-            }
+        if let Some(goal) = self.goal {
+            post_run_info.app_state =
+                goal.judge(post_run_info.app_state, self.practicing, &self.history);
+        }
+
+        if self.paused {
+            self.stop_engine_search();
+        } else if let Some(mov) = self.get_move() {
+            self.engine_move_requested = false;
+            self.make_move(mov);
         }
 
         (
             PostRunInfo {
-                position: self.position.clone(),
+                history: self.history.clone(),
+                records: self.move_records.clone(),
                 ..post_run_info.clone()
             },
-            if let app::State::GameFinish { .. } = post_run_info.app_state {
-                true
-            } else {
-                false
-            },
+            matches!(post_run_info.app_state, app::State::GameFinish { .. }),
         )
     }
 
-    fn get_player_move(&mut self) -> Option<moves::Move> {
-        match self.mouse_input_sqs.down_left {
-            Some(f_sq) => match self.mouse_input_sqs.up_left {
-                Some(t_sq) => {
-                    self.mouse_input_sqs.up_left = None;
-                    self.mouse_input_sqs.down_left = None;
-                    Some(moves::Move::from_str_move(
-                        &format!(
-                            "{}{}{}",
-                            pos::to_algn(f_sq),
-                            pos::to_algn(t_sq),
-                            if (pos::to_algn(t_sq).ends_with("8")
-                                || pos::to_algn(t_sq).ends_with("1"))
-                                && self.position.piece_on(f_sq) & piece::PAWN != 0
-                            {
-                                if self.mouse_input_sqs.keys_down.contains(&KeyCode::Q) {
-                                    "q"
-                                } else if self.mouse_input_sqs.keys_down.contains(&KeyCode::R) {
-                                    "r"
-                                } else if self.mouse_input_sqs.keys_down.contains(&KeyCode::B) {
-                                    "b"
-                                } else if self.mouse_input_sqs.keys_down.contains(&KeyCode::N) {
-                                    "n"
-                                } else {
-                                    ""
-                                }
-                            } else {
-                                ""
-                            }
-                        ),
-                        &self.position,
-                    ))
+    fn get_player_move(&mut self) -> Option<rules::Move> {
+        player_move(&mut self.mouse_input_sqs, self.history.current())
+    }
+
+    /// tells the engine to stop thinking, the move it then replies with is thrown away
+    /// and a new one is requested the next time `get_engine_move` is called
+    fn stop_engine_search(&mut self) {
+        if !self.engine_move_requested {
+            return;
+        }
+
+        if let Some(e) = self.engines[self.history.current().side()].as_mut() {
+            let _ = e.send(uci::STOP);
+            self.discard_next_engine_move = true;
+        }
+
+        self.engine_move_requested = false;
+    }
+
+    /// reads everything the engine has sent so far, keeping track of its score, and returns its
+    /// best move once it's found one
+    fn poll_engine(&mut self, idx: usize) -> Option<String> {
+        let e = self.engines[idx].as_mut()?;
+
+        while let Some(line) = e.try_get("") {
+            match engine_info::parse_line(&line) {
+                engine_info::EngineOutput::Info(info) => {
+                    if info.multipv == 1 && info.score.is_some() {
+                        self.last_engine_scores[idx] = info.score;
+                        self.last_engine_depths[idx] = Some(info.depth);
+                    }
+
+                    if let (Some(score), Some(mov), Some(line)) =
+                        (info.score, info.pv.first(), info.multipv.checked_sub(1))
+                    {
+                        let lines = &mut self.pv_lines[idx];
+                        if lines.len() <= line {
+                            lines.resize(line + 1, None);
+                        }
+                        lines[line] = Some((score, mov.clone()));
+                    }
                 }
-                None => None,
-            },
-            None => None,
+                engine_info::EngineOutput::BestMove(mov) => {
+                    let weaker = self.engine_elos[idx]
+                        .filter(|_| self.weakened_by_us[idx])
+                        .and_then(|elo| strength::pick_move(&self.pv_lines[idx], elo));
+
+                    return Some(weaker.unwrap_or(mov));
+                }
+                engine_info::EngineOutput::Other => {}
+            }
         }
+
+        None
     }
 
-    fn get_engine_move(&mut self) -> Option<moves::Move> {
-        let e = self.engines[bb::c_to_idx(self.position.side_to_move())]
-            .as_mut()
-            .unwrap();
+    /// the engine's reply once it has one, only legal moves are returned
+    fn get_engine_move(&mut self) -> Option<rules::Move> {
+        let idx = self.history.current().side();
+
+        if self.discard_next_engine_move {
+            if self.poll_engine(idx).is_some() {
+                self.discard_next_engine_move = false;
+            }
+
+            return None;
+        }
+
+        let e = self.engines[idx].as_mut().unwrap();
 
         if !self.engine_move_requested {
-            e.request_move(
-                &self.position,
-                &self.sarting_fen,
+            e.send(&self.history.position_cmd()).ok()?;
+            e.send(&format!(
+                "go wtime {} btime {}",
                 self.wtime.as_millis(),
-                self.btime.as_millis(),
-            )
+                self.btime.as_millis()
+            ))
             .ok()?;
 
             self.engine_move_requested = true;
             self.elapsed_engine_think_time = time::Duration::ZERO;
+            self.last_engine_depths[idx] = None;
+            self.last_engine_scores[idx] = None;
+            self.pv_lines[idx].clear();
         }
 
         self.elapsed_engine_think_time += self.ui_thread_delta_time;
@@ -281,13 +585,50 @@ impl Game {
             self.elapsed_engine_think_time = time::Duration::ZERO;
         }
 
-        return e.try_get_move(&self.position)?;
+        let mov = self.poll_engine(idx)?;
+
+        self.history.current().parse_uci(&mov)
     }
 
-    fn get_move(&mut self) -> Option<moves::Move> {
-        match self.engines[bb::c_to_idx(self.position.side_to_move())] {
+    fn get_move(&mut self) -> Option<rules::Move> {
+        match self.engines[self.history.current().side()] {
             Some(_) => self.get_engine_move(),
             None => self.get_player_move(),
         }
     }
 }
+
+/// turns the squares the user clicked into a move once both ends are known, a pawn reaching the
+/// last rank becomes the piece whose key is held, or a queen, and only legal moves are returned,
+/// kings are only promoted to in Antichess, and a piece held from the pocket is dropped
+pub fn player_move(
+    input_sqs: &mut app::MouseInputSquares,
+    board: &rules::Board,
+) -> Option<rules::Move> {
+    if let (Some(kind), Some(to)) = (input_sqs.held_drop, input_sqs.up_left) {
+        input_sqs.held_drop = None;
+        input_sqs.up_left = None;
+
+        return board.drop_on(kind, to);
+    }
+
+    let (from, to) = (input_sqs.down_left?, input_sqs.up_left?);
+    input_sqs.down_left = None;
+    input_sqs.up_left = None;
+
+    board.move_between(from, to, held_promotion(&input_sqs.keys_down))
+}
+
+/// the kind of piece a pawn promotes to while its key is held
+pub fn held_promotion(keys_down: &HashSet<KeyCode>) -> Option<usize> {
+    [
+        (KeyCode::Q, rules::QUEEN),
+        (KeyCode::R, rules::ROOK),
+        (KeyCode::B, rules::BISHOP),
+        (KeyCode::N, rules::KNIGHT),
+        (KeyCode::K, rules::KING),
+    ]
+    .into_iter()
+    .find(|(key, _)| keys_down.contains(key))
+    .map(|(_, kind)| kind)
+}
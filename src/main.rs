@@ -2,10 +2,28 @@ use std::io;
 
 use macroquad::{miniquad::window, prelude::*};
 
+mod analysis;
 mod app;
+mod board_editor;
+mod chess960;
+mod database;
+mod endgames;
+mod engine_info;
+mod eval_graph;
 mod events;
+mod fen;
 mod game;
+mod odds;
+mod openings;
+mod pgn;
+mod puzzles;
+mod repertoire;
+mod review;
+mod rules;
+mod search;
+mod strength;
 mod ui_skins;
+mod variant;
 mod visual_board;
 
 const WIDTH_TO_HEIGHT_RATIO: f32 = 1.8;
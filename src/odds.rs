@@ -0,0 +1,160 @@
+// handicaps the stronger side starts a game with
+
+use libchess::pos;
+
+use crate::{fen, game};
+
+// the side giving time odds gets this fraction of the other side's clock and increment
+const TIME_ODDS_DIVISOR: u64 = 5;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Odds {
+    #[default]
+    None,
+    Knight,
+    Rook,
+    Queen,
+    PawnAndMove,
+    Time,
+}
+
+impl Odds {
+    pub const ALL: [Odds; 6] = [
+        Odds::None,
+        Odds::Knight,
+        Odds::Rook,
+        Odds::Queen,
+        Odds::PawnAndMove,
+        Odds::Time,
+    ];
+
+    pub fn to_display_fmt(self) -> &'static str {
+        match self {
+            Odds::None => "None",
+            Odds::Knight => "Knight Odds",
+            Odds::Rook => "Rook Odds",
+            Odds::Queen => "Queen Odds",
+            Odds::PawnAndMove => "Pawn And Move",
+            Odds::Time => "Time Odds",
+        }
+    }
+
+    /// the white piece and file taken away, from the giver's back rank or pawn rank
+    fn removed_piece(&self) -> Option<(usize, pos::File)> {
+        match self {
+            Odds::Knight => Some((1, 1)),      // b1 knight
+            Odds::Rook => Some((3, 0)),        // a1 rook
+            Odds::Queen => Some((4, 3)),       // d1 queen
+            Odds::PawnAndMove => Some((0, 5)), // f2 pawn
+            _ => None,
+        }
+    }
+
+    /// changes the starting position or clocks in `settings`, a piece is only taken away from
+    /// the square it starts on
+    pub fn apply(&self, settings: &mut game::Settings, giver_is_white: bool) {
+        if let Some((white_piece, file)) = self.removed_piece() {
+            let Ok(mut setup) = fen::parse(&settings.position_fen) else {
+                return; // the game won't start anyway, and it'll say why
+            };
+
+            let piece = white_piece + if giver_is_white { 0 } else { 6 };
+            let rank = match (white_piece == 0, giver_is_white) {
+                (false, true) => 0,
+                (true, true) => 1,
+                (true, false) => 6,
+                (false, false) => 7,
+            };
+
+            if setup.piece_on(file, rank) == Some(piece) {
+                setup.set_piece(file, rank, None);
+
+                // without its rook the queen side can't castle
+                if *self == Odds::Rook {
+                    setup.castling[if giver_is_white { 1 } else { 3 }] = false;
+                }
+            }
+
+            if *self == Odds::PawnAndMove {
+                setup.white_to_move = !giver_is_white;
+            }
+
+            settings.position_fen = setup.to_fen();
+        }
+
+        if *self == Odds::Time {
+            // a clock can't start at zero, an increment can
+            let scaled = |s: &str, min: u64| {
+                s.parse::<u64>().map_or(s.to_string(), |n| {
+                    (n / TIME_ODDS_DIVISOR).max(min).to_string()
+                })
+            };
+
+            if giver_is_white {
+                settings.wtime_s = scaled(&settings.btime_s, 1);
+                settings.wincrement_ms = scaled(&settings.bincrement_ms, 0);
+            } else {
+                settings.btime_s = scaled(&settings.wtime_s, 1);
+                settings.bincrement_ms = scaled(&settings.wincrement_ms, 0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(odds: Odds, giver_is_white: bool, fen: &str) -> String {
+        let mut settings = game::Settings {
+            position_fen: fen.to_string(),
+            ..Default::default()
+        };
+        odds.apply(&mut settings, giver_is_white);
+
+        settings.position_fen
+    }
+
+    #[test]
+    fn material_odds_take_the_piece_off_its_square() {
+        assert_eq!(
+            applied(Odds::Knight, true, pos::START_FEN),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1"
+        );
+        assert_eq!(
+            applied(Odds::Rook, false, pos::START_FEN),
+            "1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1"
+        );
+        assert_eq!(
+            applied(Odds::PawnAndMove, true, pos::START_FEN),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPP1PP/RNBQKBNR b KQkq - 0 1"
+        );
+
+        // there's nothing on d1 to take away
+        let no_queen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1";
+        assert_eq!(applied(Odds::Queen, true, no_queen), no_queen);
+    }
+
+    #[test]
+    fn time_odds_scale_the_givers_clock() {
+        let mut settings = game::Settings {
+            wtime_s: "600".to_string(),
+            btime_s: "3".to_string(),
+            wincrement_ms: "2000".to_string(),
+            bincrement_ms: "0".to_string(),
+            ..Default::default()
+        };
+
+        Odds::Time.apply(&mut settings, false);
+        assert_eq!(
+            (settings.btime_s.as_str(), settings.bincrement_ms.as_str()),
+            ("120", "400")
+        );
+
+        Odds::Time.apply(&mut settings, true);
+        assert_eq!(
+            (settings.wtime_s.as_str(), settings.wincrement_ms.as_str()),
+            ("24", "80")
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use std::{fs, io, time};
+
+use libchess::pos;
+
+use crate::{engine_info::Score, game, openings, review, rules, variant::Variant};
+
+const GAMES_DIR: &str = "assets/games/";
+
+pub struct Tags<'a> {
+    pub white: &'a str,
+    pub black: &'a str,
+    pub result: game::GameResult,
+    pub termination: &'a str,
+    pub starting_fen: &'a str,
+    pub chess960: bool,
+    pub variant: Variant,
+    pub reviews: Option<&'a [review::MoveReview]>,
+    pub records: &'a [game::MoveRecord],
+}
+
+/// `sans` are the game's moves in order
+pub fn write(tags: &Tags, sans: &[String]) -> String {
+    let mut pgn = String::new();
+
+    pgn += "[Event \"chess_tail game\"]\n";
+    pgn += "[Site \"chess_tail\"]\n";
+    pgn += &format!("[Date \"{}\"]\n", date_today());
+    pgn += "[Round \"-\"]\n";
+    pgn += &format!("[White \"{}\"]\n", tags.white);
+    pgn += &format!("[Black \"{}\"]\n", tags.black);
+    pgn += &format!("[Result \"{}\"]\n", tags.result.to_pgn_fmt());
+    pgn += &format!("[Termination \"{}\"]\n", tags.termination);
+
+    if tags.variant != Variant::Standard {
+        pgn += &format!("[Variant \"{}\"]\n", tags.variant.to_display_fmt());
+    } else if tags.chess960 {
+        pgn += "[Variant \"Chess960\"]\n";
+    }
+
+    // chess960 games always say where the pieces started, even from the standard position
+    if tags.chess960 || tags.starting_fen != pos::START_FEN {
+        pgn += "[SetUp \"1\"]\n";
+        pgn += &format!("[FEN \"{}\"]\n", tags.starting_fen);
+    } else if tags.variant == Variant::Standard
+        && let Some(opening) = openings::classify(&sans.join(" "))
+    {
+        pgn += &format!("[ECO \"{}\"]\n", opening.eco);
+        pgn += &format!("[Opening \"{}\"]\n", opening.name);
+    }
+
+    pgn += "\n";
+
+    let mut movetext = Vec::new();
+
+    for (idx, san) in sans.iter().enumerate() {
+        let (fullmoves, white_to_move) = move_number(tags.starting_fen, idx);
+
+        if white_to_move {
+            movetext.push(format!("{fullmoves}."));
+        } else if idx == 0 {
+            movetext.push(format!("{fullmoves}..."));
+        }
+
+        movetext.push(san.clone());
+
+        let mut comment = Vec::new();
+
+        if let Some(record) = tags.records.get(idx) {
+            comment.push(format!("[%clk {}]", clock_fmt(record.clock)));
+            comment.push(format!("[%emt {}]", clock_fmt(record.think_time)));
+        }
+
+        if let Some(review) = tags.reviews.and_then(|r| r.get(idx)) {
+            if let Some(nag) = review.class.to_nag() {
+                movetext.push(nag.to_string());
+            }
+
+            comment.push(format!("[%eval {}]", eval_fmt(review.eval_after)));
+            if let (Some(best), Some(_)) = (&review.best_move_san, review.class.to_nag()) {
+                comment.push(format!(
+                    "{}. {best} was best.",
+                    review.class.to_display_fmt()
+                ));
+            }
+        }
+
+        // comments are split on spaces too so the line wrapping below can break them up
+        if !comment.is_empty() {
+            let comment = format!("{{{}}}", comment.join(" "));
+            movetext.extend(comment.split(' ').map(|t| t.to_string()));
+        }
+    }
+
+    movetext.push(tags.result.to_pgn_fmt().to_string());
+
+    // keep lines under 80 characters like most other tools do
+    let mut line_len = 0;
+    for token in movetext {
+        if line_len + token.len() + 1 > 80 {
+            pgn.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            pgn.push(' ');
+            line_len += 1;
+        }
+
+        line_len += token.len();
+        pgn += &token;
+    }
+
+    pgn + "\n"
+}
+
+/// returns the path the game was saved to
+pub fn save(pgn: &str) -> io::Result<String> {
+    fs::create_dir_all(GAMES_DIR)?;
+
+    let secs = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let path = format!("{GAMES_DIR}{secs}.pgn");
+    fs::write(&path, pgn)?;
+
+    Ok(path)
+}
+
+/// a game read from a pgn file, every variation is a whole line from the starting position
+pub struct ReadGame {
+    pub tags: Vec<(String, String)>,
+    pub start: rules::Board,
+    pub main_line: Vec<rules::Move>,
+    pub variations: Vec<Vec<rules::Move>>,
+}
+
+impl ReadGame {
+    fn new() -> Self {
+        Self {
+            tags: Vec::new(),
+            start: rules::Board::start(),
+            main_line: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the board the FEN and Variant tags set up, other tools' names for the variants included
+    fn setup(&self) -> Result<rules::Board, String> {
+        let (variant, chess960) = match self.tag("Variant").map(|v| v.to_lowercase()) {
+            None => (Variant::Standard, false),
+            Some(v) if ["chess960", "chess 960", "fischerandom"].contains(&v.as_str()) => {
+                (Variant::Standard, true)
+            }
+            Some(v) if v == "from position" => (Variant::Standard, false),
+            Some(v) => (
+                Variant::ALL
+                    .into_iter()
+                    .find(|variant| variant.to_display_fmt().to_lowercase() == v)
+                    .ok_or_else(|| format!("Unsupported Variant {v}"))?,
+                false,
+            ),
+        };
+
+        let fen = self.tag("FEN").unwrap_or(variant.start_fen());
+        rules::Board::from_fen(fen, variant, chess960).map_err(|e| format!("Bad FEN Tag: {e}"))
+    }
+
+    /// the main line played out, to be browsed or analyzed
+    pub fn history(&self) -> rules::History {
+        let mut history = rules::History::new(self.start.clone());
+        for mov in &self.main_line {
+            history.push(*mov);
+        }
+
+        history
+    }
+}
+
+/// the board after playing `line` from `start`
+fn replay(start: &rules::Board, line: &[rules::Move]) -> rules::Board {
+    let mut board = start.clone();
+    for mov in line {
+        board.make_move(*mov);
+    }
+
+    board
+}
+
+/// reads every game in `text`, comments and NAGs are skipped, a move that can't be played is an
+/// error
+pub fn read(text: &str) -> Result<Vec<ReadGame>, String> {
+    let mut games = Vec::new();
+    let mut game = ReadGame::new();
+
+    // the line being read, the ones it branched off from are on the stack
+    let mut line: Vec<rules::Move> = Vec::new();
+    let mut board: Option<rules::Board> = None;
+    let mut stack: Vec<(Vec<rules::Move>, Option<rules::Board>)> = Vec::new();
+    let mut has_moves = false;
+
+    // whatever line is left at the end of a game is its main line
+    fn finish(
+        games: &mut Vec<ReadGame>,
+        game: &mut ReadGame,
+        line: &mut Vec<rules::Move>,
+        open_variations: usize,
+    ) -> Result<(), String> {
+        if open_variations > 0 {
+            return Err("A Variation Is Never Closed".to_string());
+        }
+
+        game.main_line = std::mem::take(line);
+        games.push(std::mem::replace(game, ReadGame::new()));
+
+        Ok(())
+    }
+
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                if has_moves {
+                    finish(&mut games, &mut game, &mut line, stack.len())?;
+                    board = None;
+                    has_moves = false;
+                }
+
+                let tag = chars.by_ref().take_while(|c| *c != ']').collect::<String>();
+                let (name, value) = tag.split_once(' ').unwrap_or((&tag, ""));
+                let value = value.trim().trim_matches('"').to_string();
+
+                game.tags.push((name.to_string(), value));
+
+                if name == "FEN" || name == "Variant" {
+                    game.start = game.setup()?;
+                }
+            }
+            '{' => while chars.next().is_some_and(|c| c != '}') {},
+            ';' => while chars.next().is_some_and(|c| c != '\n') {},
+            '(' => {
+                // a variation replaces the move before it
+                let mut branch = line.clone();
+                branch.pop();
+                stack.push((line.clone(), board.clone()));
+                board = Some(replay(&game.start, &branch));
+                line = branch;
+            }
+            ')' => {
+                // only variations that got past the move they replace are kept
+                let branched_at = stack.last().map_or(0, |(l, _)| l.len().saturating_sub(1));
+                if line.len() > branched_at {
+                    game.variations.push(line.clone());
+                }
+                (line, board) = stack.pop().ok_or("A Variation Closes Twice")?;
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"(){};[".contains(*c))
+                {
+                    word.push(c);
+                }
+
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&word.as_str()) {
+                    finish(&mut games, &mut game, &mut line, stack.len())?;
+                    board = None;
+                    has_moves = false;
+                    continue;
+                }
+
+                // move numbers like "12." and "12..." can be stuck to the move
+                let word = word.rsplit('.').next().unwrap_or("");
+                if word.is_empty() || word.starts_with('$') {
+                    continue;
+                }
+
+                let current = board.get_or_insert_with(|| replay(&game.start, &line));
+                let mov = current
+                    .parse_move(word)
+                    .ok_or_else(|| format!("Couldn't Read Move {word}"))?;
+
+                current.make_move(mov);
+                line.push(mov);
+                has_moves = true;
+            }
+        }
+    }
+
+    if has_moves {
+        finish(&mut games, &mut game, &mut line, stack.len())?;
+    }
+
+    Ok(games)
+}
+
+/// the fullmove number of the move played at `ply`, and whether white is the one playing it
+pub fn move_number(starting_fen: &str, ply: usize) -> (usize, bool) {
+    let fields = starting_fen.split_whitespace().collect::<Vec<&str>>();
+    let white_first = fields.get(1).is_none_or(|s| *s == "w");
+    let fullmoves = fields
+        .get(5)
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(1);
+
+    // count plies as if white had moved first
+    let ply = ply + if white_first { 0 } else { 1 };
+
+    (fullmoves + ply / 2, ply.is_multiple_of(2))
+}
+
+/// h:mm:ss, which is what both the %clk and %emt commands expect
+fn clock_fmt(duration: time::Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// evals in pawns from white's point of view, the way lichess writes them
+fn eval_fmt(score: Score) -> String {
+    match score {
+        Score::Cp(cp) => format!("{:.2}", cp as f32 / 100.0),
+        Score::Mate(n) => format!("#{n}"),
+    }
+}
+
+fn date_today() -> String {
+    let days = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+        / 86400;
+
+    // days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!("{year}.{month:02}.{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uci(game: &ReadGame, line: &[rules::Move]) -> String {
+        let mut board = game.start.clone();
+
+        line.iter()
+            .map(|mov| {
+                let uci = board.to_uci(*mov);
+                board.make_move(*mov);
+                uci
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn reads_tags_main_lines_and_variations() {
+        let text = "[White \"A\"]\n\
+                    [Black \"B\"]\n\
+                    \n\
+                    1. e4 e5 {a comment} 2. Nf3 (2. f4 exf4) 2... Nc6 $1 3. Bb5 ; to the end\n\
+                    1-0\n\
+                    \n\
+                    [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\
+                    \n\
+                    1. e4 *\n";
+
+        let games = read(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].tag("Event"), None);
+        assert_eq!(
+            uci(&games[0], &games[0].main_line),
+            "e2e4 e7e5 g1f3 b8c6 f1b5"
+        );
+        assert_eq!(games[0].variations.len(), 1);
+        assert_eq!(
+            uci(&games[0], &games[0].variations[0]),
+            "e2e4 e7e5 f2f4 e5f4"
+        );
+
+        assert_eq!(games[1].start.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(uci(&games[1], &games[1].main_line), "e2e4");
+    }
+
+    #[test]
+    fn unreadable_games_are_errors() {
+        assert_eq!(
+            read("1. e4 e5 2. Ke3 *").err(),
+            Some("Couldn't Read Move Ke3".to_string())
+        );
+        assert_eq!(
+            read("1. e4 (1. d4 *").err(),
+            Some("A Variation Is Never Closed".to_string())
+        );
+        assert_eq!(
+            read("1. e4 ) *").err(),
+            Some("A Variation Closes Twice".to_string())
+        );
+    }
+
+    #[test]
+    fn variant_tags_set_the_rules() {
+        let text = "[Variant \"Crazyhouse\"]\n\
+                    \n\
+                    1. e4 d5 2. exd5 Qxd5 3. Nc3 Qa5 4. P@e5 *\n\
+                    \n\
+                    [Variant \"Chess960\"]\n\
+                    [FEN \"nrkbbqrn/pppppppp/8/8/8/8/PPPPPPPP/NRKBBQRN w GBgb - 0 1\"]\n\
+                    \n\
+                    1. c3 c6 2. Bc2 Bc7 3. Nb3 Nb6 4. O-O-O *\n";
+
+        let games = read(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].start.variant, Variant::Crazyhouse);
+        assert_eq!(games[0].main_line.len(), 7);
+
+        assert!(games[1].start.chess960);
+        assert_eq!(games[1].history().sans().last().unwrap(), "O-O-O");
+
+        assert!(read("[Variant \"Atomic\"]\n\n1. e4 *\n").is_err());
+    }
+}
@@ -0,0 +1,481 @@
+// tactics puzzles read from files in PUZZLES_DIR, with the solver's rating and history kept in
+// HISTORY_PATH
+//
+// two formats are read: lichess' puzzle csv (PuzzleId,FEN,Moves,Rating,...,Themes,...) where the
+// first move is the opponent's, and epd lines with a "bm" op where the first move is the solver's
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+};
+
+use crate::{rules, variant::Variant};
+
+const PUZZLES_DIR: &str = "assets/puzzles/";
+const HISTORY_PATH: &str = "assets/puzzle_history.txt";
+
+const STARTING_RATING: f32 = 1500.0;
+const RATING_K: f32 = 32.0;
+// epd files don't have ratings
+const UNRATED_PUZZLE_RATING: u32 = 1500;
+
+// once every puzzle has been tried, the least recently tried one within this many points of the
+// solver's rating comes up next
+const RETRY_RATING_RANGE: f32 = 200.0;
+
+// seconds before the opponent's reply is played, so it can be seen
+const OPPONENT_MOVE_DELAY: f32 = 0.5;
+
+#[derive(Clone)]
+pub struct Puzzle {
+    pub id: String,
+    pub fen: String,
+    pub moves: Vec<String>, // SAN or UCI, starting with the side to move in `fen`
+    pub opponent_first: bool,
+    pub rating: u32,
+    pub themes: Vec<String>,
+}
+
+fn parse_csv_line(line: &str) -> Option<Puzzle> {
+    let fields = line.split(',').collect::<Vec<&str>>();
+
+    if fields.len() < 3 || fields[0] == "PuzzleId" {
+        return None;
+    }
+
+    Some(Puzzle {
+        id: fields[0].to_string(),
+        fen: fields[1].to_string(),
+        moves: fields[2]
+            .split_whitespace()
+            .map(|m| m.to_string())
+            .collect(),
+        opponent_first: true,
+        rating: fields
+            .get(3)
+            .and_then(|r| r.parse().ok())
+            .unwrap_or(UNRATED_PUZZLE_RATING),
+        themes: fields
+            .get(7)
+            .map(|t| t.split_whitespace().map(|t| t.to_string()).collect())
+            .unwrap_or_default(),
+    })
+}
+
+/// only the first "bm" move is used, epd has no moves after it
+fn parse_epd_line(line: &str, line_number: usize) -> Option<Puzzle> {
+    let mut fields = line.splitn(5, ' ');
+    let fen = (0..4)
+        .map(|_| fields.next())
+        .collect::<Option<Vec<&str>>>()?
+        .join(" ");
+
+    let mut best_move = None;
+    let mut id = None;
+
+    for op in fields.next().unwrap_or("").split(';') {
+        let mut tokens = op.trim().splitn(2, ' ');
+
+        match (tokens.next(), tokens.next()) {
+            (Some("bm"), Some(moves)) => best_move = moves.split_whitespace().next(),
+            (Some("id"), Some(name)) => id = Some(name.trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+
+    Some(Puzzle {
+        id: id.unwrap_or_else(|| format!("epd {line_number}")),
+        fen: fen + " 0 1",
+        moves: vec![best_move?.to_string()],
+        opponent_first: false,
+        rating: UNRATED_PUZZLE_RATING,
+        themes: Vec::new(),
+    })
+}
+
+/// every puzzle in every .csv and .epd file in PUZZLES_DIR
+pub fn load_all() -> io::Result<Vec<Puzzle>> {
+    let mut puzzles = Vec::new();
+
+    for entry in fs::read_dir(PUZZLES_DIR)? {
+        let path = entry?.path();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+        if extension != "csv" && extension != "epd" {
+            continue;
+        }
+
+        for (idx, line) in fs::read_to_string(&path)?.lines().enumerate() {
+            let puzzle = if extension == "csv" {
+                parse_csv_line(line.trim())
+            } else {
+                parse_epd_line(line.trim(), idx + 1)
+            };
+
+            puzzles.extend(puzzle);
+        }
+    }
+
+    Ok(puzzles)
+}
+
+pub struct Attempt {
+    pub id: String,
+    pub solved: bool,
+    pub rating_after: f32,
+}
+
+/// every puzzle attempted, one "id,solved,rating" line each
+pub struct History {
+    pub attempts: Vec<Attempt>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        let attempts = fs::read_to_string(HISTORY_PATH)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let fields = line.split(',').collect::<Vec<&str>>();
+
+                Some(Attempt {
+                    id: fields.first()?.to_string(),
+                    solved: *fields.get(1)? == "1",
+                    rating_after: fields.get(2)?.parse().ok()?,
+                })
+            })
+            .collect();
+
+        Self { attempts }
+    }
+
+    pub fn rating(&self) -> f32 {
+        self.attempts
+            .last()
+            .map_or(STARTING_RATING, |a| a.rating_after)
+    }
+
+    /// updates the rating like elo would against a player rated as the puzzle is
+    pub fn record(&mut self, puzzle: &Puzzle, solved: bool) -> io::Result<()> {
+        let rating = self.rating();
+        let expected = 1.0 / (1.0 + 10f32.powf((puzzle.rating as f32 - rating) / 400.0));
+        let attempt = Attempt {
+            id: puzzle.id.clone(),
+            solved,
+            rating_after: rating + RATING_K * (solved as u8 as f32 - expected),
+        };
+
+        let mut file = fs::read_to_string(HISTORY_PATH).unwrap_or_default();
+        file += &format!(
+            "{},{},{:.1}\n",
+            attempt.id, attempt.solved as u8, attempt.rating_after
+        );
+        self.attempts.push(attempt);
+
+        fs::write(HISTORY_PATH, file)
+    }
+
+    /// the untried puzzle rated closest to the solver's rating, `shown` is every puzzle shown this
+    /// session in order, skipped ones included, so they count as tried too
+    ///
+    /// once every puzzle has been tried, the one tried longest ago near the solver's rating comes
+    /// up again
+    pub fn next_puzzle<'a>(
+        &self,
+        puzzles: &'a [Puzzle],
+        broken: &HashSet<String>,
+        shown: &[String],
+    ) -> Option<&'a Puzzle> {
+        let rating = self.rating();
+        let distance = |p: &Puzzle| (p.rating as f32 - rating).abs();
+
+        // how recently each puzzle was tried, higher is more recent
+        let mut last_tried = HashMap::new();
+        for (idx, id) in self.attempts.iter().map(|a| &a.id).chain(shown).enumerate() {
+            last_tried.insert(id.as_str(), idx);
+        }
+
+        let playable = puzzles
+            .iter()
+            .filter(|p| !broken.contains(&p.id))
+            .collect::<Vec<&Puzzle>>();
+
+        let untried = playable
+            .iter()
+            .filter(|p| !last_tried.contains_key(p.id.as_str()))
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+
+        if let Some(p) = untried {
+            return Some(p);
+        }
+
+        let near = playable
+            .iter()
+            .filter(|p| distance(p) <= RETRY_RATING_RANGE)
+            .collect::<Vec<_>>();
+        let retry_from = if near.is_empty() {
+            playable.iter().collect()
+        } else {
+            near
+        };
+
+        retry_from
+            .into_iter()
+            .min_by(|a, b| {
+                last_tried[a.id.as_str()]
+                    .cmp(&last_tried[b.id.as_str()])
+                    .then(distance(a).total_cmp(&distance(b)))
+            })
+            .copied()
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Status {
+    Solving,
+    Solved,
+    SolutionShown,
+}
+
+/// one puzzle being solved
+pub struct Session {
+    pub puzzle: Puzzle,
+    pub history: rules::History,
+    pub solver: usize, // 0 for white
+    pub status: Status,
+    pub mistakes: u32,
+    pub last_move_wrong: bool,
+    pub recorded: bool,
+    next: usize, // index into `puzzle.moves`
+    opponent_timer: f32,
+}
+
+impl Session {
+    /// fails if the puzzle's position or any move of its solution can't be played
+    pub fn new(puzzle: &Puzzle) -> Result<Self, String> {
+        let board = rules::Board::from_fen(&puzzle.fen, Variant::Standard, false)
+            .map_err(|e| e.to_string())?;
+        let solver = if puzzle.opponent_first {
+            1 - board.side()
+        } else {
+            board.side()
+        };
+
+        let mut session = Self {
+            puzzle: puzzle.clone(),
+            history: rules::History::new(board.clone()),
+            solver,
+            status: Status::Solving,
+            mistakes: 0,
+            last_move_wrong: false,
+            recorded: false,
+            next: 0,
+            opponent_timer: 0.0,
+        };
+
+        // played out up front so a broken solution never gets as far as being recorded
+        while session.next < puzzle.moves.len() {
+            let Some(mov) = session.expected_move() else {
+                return Err(format!(
+                    "Its Solution Has An Illegal Move, {}",
+                    puzzle.moves[session.next]
+                ));
+            };
+            session.play(mov);
+        }
+
+        session.history = rules::History::new(board);
+        session.status = Status::Solving;
+        session.next = 0;
+
+        Ok(session)
+    }
+
+    pub fn is_solvers_turn(&self) -> bool {
+        self.status == Status::Solving && self.history.current().side() == self.solver
+    }
+
+    /// whether the puzzle counts as solved, once that's known, a single mistake fails it
+    pub fn result(&self) -> Option<bool> {
+        if self.mistakes > 0 || self.status == Status::SolutionShown {
+            Some(false)
+        } else if self.status == Status::Solved {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    fn expected_move(&self) -> Option<rules::Move> {
+        self.history
+            .current()
+            .parse_move(self.puzzle.moves.get(self.next)?)
+    }
+
+    fn play(&mut self, mov: rules::Move) {
+        self.history.push(mov);
+        self.next += 1;
+
+        if self.next >= self.puzzle.moves.len() && self.status == Status::Solving {
+            self.status = Status::Solved;
+        }
+    }
+
+    /// plays the opponent's move once it's been waited for, returning it
+    pub fn update(&mut self, delta_time: f32) -> Option<rules::Move> {
+        if self.status != Status::Solving || self.is_solvers_turn() {
+            return None;
+        }
+
+        self.opponent_timer += delta_time;
+        if self.opponent_timer < OPPONENT_MOVE_DELAY {
+            return None;
+        }
+        self.opponent_timer = 0.0;
+
+        // every move of the solution was checked to be playable when the session was made
+        let mov = self.expected_move()?;
+        self.play(mov);
+
+        Some(mov)
+    }
+
+    /// plays `mov` if it's the solution's move, or any other move that mates, returns whether it
+    /// was played
+    pub fn try_move(&mut self, mov: rules::Move) -> bool {
+        let board = self.history.current();
+        if !self.is_solvers_turn() || !board.legal_moves().contains(&mov) {
+            return false;
+        }
+
+        let mut after = board.clone();
+        after.make_move(mov);
+        let mates = after.in_check(after.side()) && after.legal_moves().is_empty();
+
+        if Some(mov) == self.expected_move() || mates {
+            self.last_move_wrong = false;
+            self.play(mov);
+
+            if mates {
+                self.status = Status::Solved;
+            }
+
+            true
+        } else {
+            self.last_move_wrong = true;
+            self.mistakes += 1;
+
+            false
+        }
+    }
+
+    /// plays out the rest of the solution
+    pub fn show_solution(&mut self) {
+        self.status = Status::SolutionShown;
+
+        while let Some(mov) = self.expected_move() {
+            self.play(mov);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libchess::pos;
+
+    use super::*;
+
+    fn puzzle(id: &str, rating: u32) -> Puzzle {
+        Puzzle {
+            id: id.to_string(),
+            fen: pos::START_FEN.to_string(),
+            moves: vec!["e2e4".to_string()],
+            opponent_first: false,
+            rating,
+            themes: Vec::new(),
+        }
+    }
+
+    fn history(ids: &[&str]) -> History {
+        History {
+            attempts: ids
+                .iter()
+                .map(|id| Attempt {
+                    id: id.to_string(),
+                    solved: true,
+                    rating_after: STARTING_RATING,
+                })
+                .collect(),
+        }
+    }
+
+    fn next(history: &History, puzzles: &[Puzzle], broken: &[&str], shown: &[&str]) -> String {
+        let broken = broken.iter().map(|id| id.to_string()).collect();
+        let shown = shown
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>();
+
+        history
+            .next_puzzle(puzzles, &broken, &shown)
+            .unwrap()
+            .id
+            .clone()
+    }
+
+    #[test]
+    fn untried_puzzle_closest_to_rating_comes_first() {
+        let puzzles = [puzzle("a", 1000), puzzle("b", 1550), puzzle("c", 1480)];
+
+        assert_eq!(next(&history(&[]), &puzzles, &[], &[]), "c");
+        assert_eq!(next(&history(&["c"]), &puzzles, &[], &[]), "b");
+    }
+
+    #[test]
+    fn skipped_puzzles_dont_come_straight_back() {
+        let puzzles = [puzzle("a", 1500), puzzle("b", 1600)];
+
+        assert_eq!(next(&history(&[]), &puzzles, &[], &["a"]), "b");
+        assert_eq!(next(&history(&[]), &puzzles, &[], &["a", "b"]), "a");
+        assert_eq!(next(&history(&[]), &puzzles, &["a"], &["b"]), "b");
+    }
+
+    #[test]
+    fn least_recently_tried_puzzle_near_rating_comes_back() {
+        let puzzles = [puzzle("a", 1500), puzzle("b", 1510), puzzle("far", 2500)];
+
+        // "far" was tried longest ago but is out of range
+        assert_eq!(next(&history(&["far", "b", "a"]), &puzzles, &[], &[]), "b");
+        assert_eq!(
+            next(&history(&["far", "b", "a"]), &puzzles, &[], &["b"]),
+            "a"
+        );
+    }
+
+    #[test]
+    fn nothing_playable_gives_none() {
+        let puzzles = [puzzle("a", 1500)];
+        let broken = HashSet::from(["a".to_string()]);
+
+        assert!(history(&[]).next_puzzle(&puzzles, &broken, &[]).is_none());
+        assert!(
+            history(&[])
+                .next_puzzle(&[], &HashSet::new(), &[])
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn csv_and_epd_lines_parse() {
+        let p =
+            parse_csv_line("00sHx,q3k1nr/8/8/8/8/8/8/4K3 w - - 0 1,e1e2 e8e7,1760,80,83,72,mate")
+                .unwrap();
+        assert_eq!((p.id.as_str(), p.rating, p.moves.len()), ("00sHx", 1760, 2));
+        assert!(p.opponent_first);
+        assert!(parse_csv_line("PuzzleId,FEN,Moves").is_none());
+
+        let p = parse_epd_line("4k3/8/8/8/8/8/8/4K2R w K - bm O-O; id \"castle\";", 3).unwrap();
+        assert_eq!((p.id.as_str(), p.moves[0].as_str()), ("castle", "O-O"));
+        assert!(!p.opponent_first);
+    }
+}
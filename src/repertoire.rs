@@ -0,0 +1,344 @@
+// drilling an opening repertoire from a pgn file in REPERTOIRES_DIR, every line from the start to
+// the end of a variation is drilled on its own, and lines come back sooner the more often they've
+// been gotten wrong (how soon is kept in PROGRESS_PATH)
+
+use std::{collections::HashMap, fs, io, time};
+
+use crate::{pgn, rules};
+
+const REPERTOIRES_DIR: &str = "assets/repertoires/";
+const PROGRESS_PATH: &str = "assets/repertoire_progress.txt";
+
+// days until a line comes up again, by how many times in a row it's been played right
+const REVIEW_INTERVALS_DAYS: [u64; 7] = [0, 1, 3, 7, 14, 30, 90];
+const SECS_PER_DAY: u64 = 86400;
+
+// seconds before the opponent's move is played, so it can be seen
+const OPPONENT_MOVE_DELAY: f32 = 0.5;
+
+fn now_secs() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// the names of the pgn files that can be drilled
+pub fn list_files() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(REPERTOIRES_DIR) else {
+        return Vec::new();
+    };
+
+    let mut files = entries
+        .filter_map(|e| e.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".pgn"))
+        .collect::<Vec<String>>();
+    files.sort();
+
+    files
+}
+
+#[derive(Clone)]
+pub struct Line {
+    pub start: rules::Board,
+    pub moves: Vec<rules::Move>,
+    pub sans: String, // the moves written out, also what the line is known by
+}
+
+/// every line in `file`, the main lines and every variation of every game
+pub fn load(file: &str) -> Result<Vec<Line>, String> {
+    let text = fs::read_to_string(format!("{REPERTOIRES_DIR}{file}")).map_err(|e| e.to_string())?;
+    let mut lines: Vec<Line> = Vec::new();
+
+    for read in pgn::read(&text)? {
+        for moves in [read.main_line].into_iter().chain(read.variations) {
+            let mut history = rules::History::new(read.start.clone());
+            for mov in &moves {
+                history.push(*mov);
+            }
+
+            let sans = history.sans().join(" ");
+            if !moves.is_empty() && lines.iter().all(|l| l.sans != sans) {
+                lines.push(Line {
+                    start: read.start.clone(),
+                    moves,
+                    sans,
+                });
+            }
+        }
+    }
+
+    Ok(lines)
+}
+
+/// whether `user` still has a move to play in `line` once `from_ply` moves have been played
+fn has_move_for(line: &Line, from_ply: usize, user: usize) -> bool {
+    if (line.start.side() + from_ply) % 2 == user {
+        from_ply < line.moves.len()
+    } else {
+        from_ply + 1 < line.moves.len()
+    }
+}
+
+struct Schedule {
+    streak: usize,
+    due: u64, // unix seconds
+}
+
+/// when every line that's been drilled is due again, one "streak,due,key" line each
+pub struct Progress {
+    schedules: HashMap<String, Schedule>,
+}
+
+impl Progress {
+    pub fn load() -> Self {
+        let schedules = fs::read_to_string(PROGRESS_PATH)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, ',');
+                let streak = fields.next()?.parse().ok()?;
+                let due = fields.next()?.parse().ok()?;
+
+                Some((fields.next()?.to_string(), Schedule { streak, due }))
+            })
+            .collect();
+
+        Self { schedules }
+    }
+
+    /// lines that were never drilled are due right away
+    fn due(&self, key: &str) -> u64 {
+        self.schedules.get(key).map_or(0, |s| s.due)
+    }
+
+    pub fn due_count(&self, keys: &[String]) -> usize {
+        let now = now_secs();
+        keys.iter().filter(|k| self.due(k) <= now).count()
+    }
+
+    /// a line played right waits longer than last time, a mistake brings it back right away
+    pub fn record(&mut self, key: &str, correct: bool) -> io::Result<()> {
+        let streak = if correct {
+            self.schedules.get(key).map_or(0, |s| s.streak) + 1
+        } else {
+            0
+        };
+        let days = REVIEW_INTERVALS_DAYS[streak.min(REVIEW_INTERVALS_DAYS.len() - 1)];
+
+        self.schedules.insert(
+            key.to_string(),
+            Schedule {
+                streak,
+                due: now_secs() + days * SECS_PER_DAY,
+            },
+        );
+
+        let mut file = String::new();
+        for (key, s) in &self.schedules {
+            file += &format!("{},{},{key}\n", s.streak, s.due);
+        }
+
+        fs::write(PROGRESS_PATH, file)
+    }
+}
+
+/// what a line is known by in the progress file, the same line is drilled separately per side
+pub fn key(file: &str, user: usize, line: &Line) -> String {
+    let side = if user == 0 { "w" } else { "b" };
+    format!("{file} {side} {}", line.sans)
+}
+
+/// the line that's been due the longest, lines with nothing for `user` to play are left out and
+/// `current` only comes up again if there's no other line
+pub fn next_line<'a>(
+    lines: &'a [Line],
+    progress: &Progress,
+    file: &str,
+    user: usize,
+    current: Option<&Line>,
+) -> Option<&'a Line> {
+    let trainable = lines
+        .iter()
+        .filter(|l| has_move_for(l, 0, user))
+        .collect::<Vec<&Line>>();
+    let due_longest = |candidates: Vec<&'a Line>| {
+        candidates
+            .into_iter()
+            .min_by_key(|l| progress.due(&key(file, user, l)))
+    };
+
+    let others = trainable
+        .iter()
+        .copied()
+        .filter(|l| current.is_none_or(|c| c.sans != l.sans))
+        .collect();
+
+    due_longest(others).or_else(|| due_longest(trainable))
+}
+
+/// one line being drilled
+pub struct Drill {
+    pub line: Line,
+    pub history: rules::History,
+    pub user: usize, // 0 for white
+    pub mistakes: u32,
+    pub hint: Option<String>, // the move that was expected after a wrong one
+    pub done: bool,
+    pub recorded: bool,
+    next: usize, // index into `line.moves`
+    opponent_timer: f32,
+}
+
+impl Drill {
+    pub fn new(line: &Line, user: usize) -> Self {
+        Self {
+            line: line.clone(),
+            history: rules::History::new(line.start.clone()),
+            user,
+            mistakes: 0,
+            hint: None,
+            done: false,
+            recorded: false,
+            next: 0,
+            opponent_timer: 0.0,
+        }
+    }
+
+    pub fn is_users_turn(&self) -> bool {
+        !self.done && self.history.current().side() == self.user
+    }
+
+    fn play(&mut self, mov: rules::Move) {
+        self.history.push(mov);
+        self.next += 1;
+
+        // the opponent's last move isn't worth waiting for
+        self.done = !has_move_for(&self.line, self.next, self.user);
+    }
+
+    /// plays the opponent's repertoire move once it's been waited for, returning it
+    pub fn update(&mut self, delta_time: f32) -> Option<rules::Move> {
+        if self.done || self.is_users_turn() {
+            return None;
+        }
+
+        self.opponent_timer += delta_time;
+        if self.opponent_timer < OPPONENT_MOVE_DELAY {
+            return None;
+        }
+        self.opponent_timer = 0.0;
+
+        let mov = self.line.moves[self.next];
+        self.play(mov);
+
+        Some(mov)
+    }
+
+    /// plays `mov` if it's the line's move, or the move of another line in `lines` that got here
+    /// the same way, which is drilled from then on, returns whether it was played
+    pub fn try_move(&mut self, mov: rules::Move, lines: &[Line]) -> bool {
+        if !self.is_users_turn() || !self.history.current().legal_moves().contains(&mov) {
+            return false;
+        }
+
+        let expected = self.line.moves[self.next];
+        let played = &self.line.moves[..self.next];
+        let sibling = lines.iter().find(|l| {
+            l.start.to_fen() == self.line.start.to_fen()
+                && l.moves.len() > self.next
+                && l.moves[..self.next] == *played
+                && l.moves[self.next] == mov
+        });
+
+        if mov == expected {
+            self.hint = None;
+            self.play(mov);
+            true
+        } else if let Some(line) = sibling {
+            self.line = line.clone();
+            self.hint = None;
+            self.play(mov);
+            true
+        } else {
+            self.hint = Some(self.history.current().to_san(expected));
+            self.mistakes += 1;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // only how many moves a line has matters for picking the next one
+    fn line(sans: &str) -> Line {
+        let e4 = rules::Move::Normal {
+            from: rules::square(4, 1),
+            to: rules::square(4, 3),
+            promotion: None,
+        };
+
+        Line {
+            start: rules::Board::start(),
+            moves: sans.split_whitespace().map(|_| e4).collect(),
+            sans: sans.to_string(),
+        }
+    }
+
+    fn progress(dues: &[(&Line, u64)]) -> Progress {
+        Progress {
+            schedules: dues
+                .iter()
+                .map(|(l, due)| {
+                    (
+                        key("r.pgn", 0, l),
+                        Schedule {
+                            streak: 1,
+                            due: *due,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn next(lines: &[Line], progress: &Progress, current: Option<&Line>) -> String {
+        next_line(lines, progress, "r.pgn", 0, current)
+            .unwrap()
+            .sans
+            .clone()
+    }
+
+    #[test]
+    fn line_due_longest_comes_first() {
+        let lines = [line("e4 e5 Nf3"), line("e4 c5 Nf3"), line("d4 d5 c4")];
+
+        let p = progress(&[(&lines[0], 300), (&lines[1], 100), (&lines[2], 200)]);
+        assert_eq!(next(&lines, &p, None), "e4 c5 Nf3");
+
+        // never drilled is due right away
+        let p = progress(&[(&lines[0], 300), (&lines[1], 100)]);
+        assert_eq!(next(&lines, &p, None), "d4 d5 c4");
+    }
+
+    #[test]
+    fn current_line_is_skipped_unless_its_the_only_one() {
+        let lines = [line("e4 e5 Nf3"), line("d4 d5 c4")];
+        let p = progress(&[]);
+
+        assert_eq!(next(&lines, &p, Some(&lines[0])), "d4 d5 c4");
+        assert_eq!(next(&lines, &p, Some(&lines[1])), "e4 e5 Nf3");
+        assert_eq!(next(&lines[..1], &p, Some(&lines[0])), "e4 e5 Nf3");
+    }
+
+    #[test]
+    fn lines_with_nothing_to_play_are_left_out() {
+        let lines = [line("e4")];
+
+        // black never gets to move in a one move line
+        assert!(next_line(&lines, &progress(&[]), "r.pgn", 1, None).is_none());
+        assert_eq!(next(&lines, &progress(&[]), None), "e4");
+    }
+}
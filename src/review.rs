@@ -0,0 +1,337 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread, time,
+};
+
+use libchess::{color as chess_color, uci};
+
+use crate::{
+    engine_info::{self, EngineOutput, Score},
+    rules,
+};
+
+// thresholds on the drop in winning chances (0 to 100) caused by a move
+const INACCURACY_LOSS: f32 = 5.0;
+const MISTAKE_LOSS: f32 = 10.0;
+const BLUNDER_LOSS: f32 = 15.0;
+
+const ENGINE_REPLY_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveClass {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClass {
+    fn from_loss(loss: f32) -> Self {
+        if loss >= BLUNDER_LOSS {
+            MoveClass::Blunder
+        } else if loss >= MISTAKE_LOSS {
+            MoveClass::Mistake
+        } else if loss >= INACCURACY_LOSS {
+            MoveClass::Inaccuracy
+        } else {
+            MoveClass::Good
+        }
+    }
+
+    /// the symbol that goes after the move in the move list
+    pub fn to_symbol(self) -> &'static str {
+        match self {
+            MoveClass::Best | MoveClass::Good => "",
+            MoveClass::Inaccuracy => "?!",
+            MoveClass::Mistake => "?",
+            MoveClass::Blunder => "??",
+        }
+    }
+
+    pub fn to_nag(self) -> Option<&'static str> {
+        match self {
+            MoveClass::Best | MoveClass::Good => None,
+            MoveClass::Inaccuracy => Some("$6"),
+            MoveClass::Mistake => Some("$2"),
+            MoveClass::Blunder => Some("$4"),
+        }
+    }
+
+    pub fn to_display_fmt(self) -> &'static str {
+        match self {
+            MoveClass::Best => "Best",
+            MoveClass::Good => "Good",
+            MoveClass::Inaccuracy => "Inaccuracy",
+            MoveClass::Mistake => "Mistake",
+            MoveClass::Blunder => "Blunder",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MoveReview {
+    pub san: String,
+    pub class: MoveClass,
+    pub win_loss: f32,
+    pub eval_after: Score,             // from white's point of view
+    pub best_move_san: Option<String>, // only when the move played wasn't the engine's choice
+}
+
+/// what the engine thought of one position, from white's point of view
+#[derive(Clone)]
+struct PositionEval {
+    score: Score,
+    best_move: Option<String>,
+}
+
+#[derive(Default)]
+struct Progress {
+    evals: Vec<PositionEval>,
+    error: Option<String>,
+}
+
+/// reviews a finished game on its own thread, the results are picked up by calling `update`
+pub struct Review {
+    progress: Arc<Mutex<Progress>>,
+    cancel: Arc<Mutex<bool>>,
+    history: rules::History,
+    pub reviews: Vec<MoveReview>,
+}
+
+impl Review {
+    pub fn start(engine_path: &str, movetime: time::Duration, history: &rules::History) -> Self {
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let cancel = Arc::new(Mutex::new(false));
+
+        {
+            let progress = progress.clone();
+            let cancel = cancel.clone();
+            let engine_path = engine_path.to_string();
+            let history = history.clone();
+
+            thread::spawn(move || {
+                if let Err(e) = evaluate_game(&engine_path, movetime, &history, &progress, &cancel)
+                {
+                    progress.lock().unwrap().error = Some(e);
+                }
+            });
+        }
+
+        Self {
+            progress,
+            cancel,
+            history: history.clone(),
+            reviews: Vec::new(),
+        }
+    }
+
+    /// classifies every move whose position before and after has been evaluated
+    pub fn update(&mut self) {
+        let evals = self.progress.lock().unwrap().evals.clone();
+
+        while self.reviews.len() + 1 < evals.len() {
+            let ply = self.reviews.len();
+            let (board, mov) = (&self.history.boards[ply], self.history.moves[ply]);
+            let (before, after) = (&evals[ply], &evals[ply + 1]);
+
+            // both scores as seen by the side that played the move
+            let (before_score, after_score) = if board.white_to_move {
+                (before.score, after.score)
+            } else {
+                (before.score.negated(), after.score.negated())
+            };
+
+            // mate scores lose their sign at zero, so checkmate has to be caught on its own
+            let delivers_mate = after.best_move.is_none() && after.score == Score::Mate(0);
+
+            let win_loss = if delivers_mate {
+                0.0
+            } else {
+                (win_percent(before_score) - win_percent(after_score)).max(0.0)
+            };
+            let is_best = delivers_mate
+                || before
+                    .best_move
+                    .as_ref()
+                    .is_some_and(|m| board.parse_uci(m) == Some(mov));
+
+            let best_move_san = match &before.best_move {
+                Some(best) if !is_best => {
+                    let best = board.line_to_san(std::slice::from_ref(best), 1);
+                    if best.is_empty() { None } else { Some(best) }
+                }
+                _ => None,
+            };
+
+            self.reviews.push(MoveReview {
+                san: board.to_san(mov),
+                class: if is_best {
+                    MoveClass::Best
+                } else {
+                    MoveClass::from_loss(win_loss)
+                },
+                win_loss,
+                eval_after: after.score,
+                best_move_san,
+            });
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.reviews.len() == self.history.ply()
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.progress.lock().unwrap().error.clone()
+    }
+
+    /// the average accuracy of a side's moves so far, from 0 to 100
+    pub fn accuracy(&self, color: chess_color::Color) -> Option<f32> {
+        let white_moves_first = self.history.boards[0].white_to_move;
+        let parity = if (color == chess_color::WHITE) == white_moves_first {
+            0
+        } else {
+            1
+        };
+
+        let accuracies = self
+            .reviews
+            .iter()
+            .enumerate()
+            .filter(|(ply, _)| ply % 2 == parity)
+            .map(|(_, r)| (103.1668 * (-0.04354 * r.win_loss).exp() - 3.1669).clamp(0.0, 100.0))
+            .collect::<Vec<f32>>();
+
+        if accuracies.is_empty() {
+            None
+        } else {
+            Some(accuracies.iter().sum::<f32>() / accuracies.len() as f32)
+        }
+    }
+}
+
+impl Drop for Review {
+    fn drop(&mut self) {
+        *self.cancel.lock().unwrap() = true;
+    }
+}
+
+/// chances of winning from 0 to 100, see https://lichess.org/page/accuracy
+pub fn win_percent(score: Score) -> f32 {
+    match score {
+        Score::Cp(cp) => {
+            let cp = cp.clamp(-1000, 1000) as f32;
+            50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp).exp()) - 1.0)
+        }
+        Score::Mate(n) => {
+            if n > 0 {
+                100.0
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+fn wait_for(engine: &mut uci::Engine, expected: &str) -> Result<(), String> {
+    let start = time::Instant::now();
+
+    while engine.try_get(expected).is_none() {
+        if start.elapsed() > ENGINE_REPLY_TIMEOUT {
+            return Err(format!("Engine Didn't Reply With '{expected}'"));
+        }
+
+        thread::sleep(time::Duration::from_millis(1));
+    }
+
+    Ok(())
+}
+
+fn evaluate_game(
+    engine_path: &str,
+    movetime: time::Duration,
+    history: &rules::History,
+    progress: &Mutex<Progress>,
+    cancel: &Mutex<bool>,
+) -> Result<(), String> {
+    let mut engine = uci::Engine::new(engine_path).map_err(|e| e.to_string())?;
+
+    engine.send(uci::UCI).map_err(|e| e.to_string())?;
+    wait_for(&mut engine, uci::UCI_OK)?;
+    if history.boards[0].chess960 {
+        engine
+            .send("setoption name UCI_Chess960 value true")
+            .map_err(|e| e.to_string())?;
+    }
+    if let Some(name) = history.boards[0].variant.to_uci_fmt() {
+        engine
+            .send(&format!("setoption name UCI_Variant value {name}"))
+            .map_err(|e| e.to_string())?;
+    }
+    engine.send(uci::NEW_GAME).map_err(|e| e.to_string())?;
+    engine.send(uci::IS_READY).map_err(|e| e.to_string())?;
+    wait_for(&mut engine, uci::READY_OK)?;
+
+    for (ply, board) in history.boards.iter().enumerate() {
+        if *cancel.lock().unwrap() {
+            break;
+        }
+
+        // finished games can end in positions there's nothing to search in
+        let eval = if board.legal_moves().is_empty() {
+            PositionEval {
+                score: if board.in_check(board.side()) {
+                    Score::Mate(0)
+                } else {
+                    Score::Cp(0)
+                },
+                best_move: None,
+            }
+        } else {
+            engine
+                .send(&history.position_cmd_at(ply))
+                .map_err(|e| e.to_string())?;
+            engine
+                .send(&format!("go movetime {}", movetime.as_millis()))
+                .map_err(|e| e.to_string())?;
+
+            let mut score = Score::Cp(0);
+            let start = time::Instant::now();
+
+            let best_move = loop {
+                if let Some(line) = engine.try_get("") {
+                    match engine_info::parse_line(&line) {
+                        EngineOutput::Info(info) if info.multipv == 1 => {
+                            score = info.score.unwrap_or(score);
+                        }
+                        EngineOutput::BestMove(mov) => break mov,
+                        _ => {}
+                    }
+                } else if start.elapsed() > movetime + ENGINE_REPLY_TIMEOUT {
+                    return Err("Engine Stopped Responding".to_string());
+                } else {
+                    thread::sleep(time::Duration::from_millis(1));
+                }
+            };
+
+            PositionEval {
+                score,
+                best_move: Some(best_move),
+            }
+        };
+
+        progress.lock().unwrap().evals.push(PositionEval {
+            score: if board.white_to_move {
+                eval.score
+            } else {
+                eval.score.negated()
+            },
+            ..eval
+        });
+    }
+
+    let _ = engine.send("quit");
+
+    Ok(())
+}
@@ -0,0 +1,1383 @@
+// the rules every game is played by, libchess's moves can only castle with the king and rooks
+// on their usual squares so chess960 couldn't be played with them, and only know standard chess
+// so neither could Horde's kingless army of pawns, Antichess where captures are forced and the
+// king is just another piece, or Crazyhouse where taken pieces can be dropped back on the board
+//
+// squares are rank * 8 + file, and pieces are indexed like the piece textures, white pieces first
+
+use libchess::{color as chess_color, pos};
+
+use crate::{
+    board_editor::PIECE_CHARS,
+    fen::{self, FenError, FenField},
+    game::GameResult,
+    variant::Variant,
+};
+
+pub type Square = usize;
+
+pub const PAWN: usize = 0;
+pub const KNIGHT: usize = 1;
+pub const BISHOP: usize = 2;
+pub const ROOK: usize = 3;
+pub const QUEEN: usize = 4;
+pub const KING: usize = 5;
+
+const KNIGHT_STEPS: [(isize, isize); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+const BISHOP_DIRECTIONS: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const ROOK_DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, -1), (-1, 0), (0, 1)];
+
+// indexed by piece % 6, same values libchess gives `material_diff`
+const PIECE_VALUES: [i32; 6] = [1, 3, 3, 5, 9, 0];
+
+pub const FIFTY_MOVE_PLIES: u32 = 100;
+
+// the second index of `Board::castling`
+const KINGSIDE: usize = 0;
+const QUEENSIDE: usize = 1;
+
+pub fn square(file: usize, rank: usize) -> Square {
+    rank * 8 + file
+}
+
+pub fn file_of(sq: Square) -> usize {
+    sq % 8
+}
+
+pub fn rank_of(sq: Square) -> usize {
+    sq / 8
+}
+
+pub fn to_algn(sq: Square) -> String {
+    format!("{}{}", (file_of(sq) as u8 + b'a') as char, rank_of(sq) + 1)
+}
+
+/// the square `step` away from `sq`, if that's still on the board
+fn offset(sq: Square, (df, dr): (isize, isize)) -> Option<Square> {
+    let f = file_of(sq) as isize + df;
+    let r = rank_of(sq) as isize + dr;
+
+    ((0..8).contains(&f) && (0..8).contains(&r)).then(|| square(f as usize, r as usize))
+}
+
+fn back_rank(color: usize) -> usize {
+    if color == 0 { 0 } else { 7 }
+}
+
+/// where the king and rook end up when castling, same as in standard chess whatever files they
+/// start on
+pub fn castled_squares(king: Square, rook: Square) -> (Square, Square) {
+    let rank = rank_of(king);
+
+    if rook > king {
+        (square(6, rank), square(5, rank))
+    } else {
+        (square(2, rank), square(3, rank))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Move {
+    Normal {
+        from: Square,
+        to: Square,
+        promotion: Option<usize>, // the kind of piece, like `QUEEN`
+    },
+    Castle {
+        king: Square,
+        rook: Square,
+    },
+    Drop {
+        kind: usize, // like `KNIGHT`, taken out of the mover's pocket
+        to: Square,
+    },
+}
+
+impl Move {
+    /// where the moving piece comes from, nowhere on the board for a drop
+    pub fn source(self) -> Option<Square> {
+        match self {
+            Move::Normal { from, .. } => Some(from),
+            Move::Castle { king, .. } => Some(king),
+            Move::Drop { .. } => None,
+        }
+    }
+
+    /// where the moving piece ends up, the king's new square for castling
+    pub fn target(self) -> Square {
+        match self {
+            Move::Normal { to, .. } | Move::Drop { to, .. } => to,
+            Move::Castle { king, rook } => castled_squares(king, rook).0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Board {
+    pub squares: [Option<usize>; 64],
+    pub white_to_move: bool,
+    castling: [[Option<usize>; 2]; 2], // the files of the rooks that can still castle, by color
+    en_passant: Option<Square>,        // only set when a pawn can take there
+    pub halfmoves: u32,
+    fullmoves: u32,
+    pub checks: [u32; 2],      // given by white and black so far
+    pub pockets: [[u8; 5]; 2], // the pieces white and black can drop, by kind up to `QUEEN`
+    promoted: u64,             // squares with promoted pieces, they're pocketed as pawns
+    pub variant: Variant,
+    pub chess960: bool,
+}
+
+impl Board {
+    /// castling rights can be written as KQkq, which is the outermost rook on that side, or as
+    /// the rook's file (Shredder-FEN). Crazyhouse pockets go in brackets after the pieces, like
+    /// `RNBQKBNR[Qn]`, with a `~` after each promoted piece
+    pub fn from_fen(fen: &str, variant: Variant, chess960: bool) -> Result<Self, FenError> {
+        let (setup, pockets, promoted) = if variant == Variant::Crazyhouse {
+            let (fen, pockets, promoted) = split_pockets(fen)?;
+            (fen::parse(&fen)?, pockets, promoted)
+        } else {
+            (fen::parse(fen)?, [[0; 5]; 2], 0)
+        };
+
+        let mut board = Board {
+            squares: [None; 64],
+            white_to_move: setup.white_to_move,
+            castling: [[None; 2]; 2],
+            en_passant: setup
+                .en_passant
+                .map(|(f, r)| square(f as usize, r as usize)),
+            halfmoves: setup.halfmoves,
+            fullmoves: setup.fullmoves,
+            checks: [0; 2],
+            pockets,
+            promoted,
+            variant,
+            chess960,
+        };
+
+        for r in 0..8 {
+            for f in 0..8 {
+                board.squares[square(f, r)] = setup.board[r][f];
+            }
+        }
+
+        // horde is white's side in Horde, it has no king and as many pawns as it likes
+        let is_horde = |color: usize| variant == Variant::Horde && color == 0;
+
+        for (color, name) in [(0, "White"), (1, "Black")] {
+            if is_horde(color) {
+                if board.count(KING) > 0 {
+                    return Err(FenError::new(
+                        FenField::Placement,
+                        "White Can't Have A King In Horde",
+                    ));
+                }
+            } else if variant != Variant::Antichess && board.count(KING + color * 6) != 1 {
+                return Err(FenError::new(
+                    FenField::Placement,
+                    format!("{name} Must Have Exactly One King"),
+                ));
+            }
+
+            // pawns taken from the other side can be dropped in Crazyhouse
+            if !is_horde(color)
+                && variant != Variant::Crazyhouse
+                && board.count(PAWN + color * 6) > 8
+            {
+                return Err(FenError::new(
+                    FenField::Placement,
+                    "A Side Can't Have More Than Eight Pawns",
+                ));
+            }
+        }
+
+        if [0, 7].into_iter().any(|r| {
+            (0..8).any(|f| {
+                board.squares[square(f, r)]
+                    .is_some_and(|p| p % 6 == PAWN && !(r == 0 && is_horde(p / 6)))
+            })
+        }) {
+            return Err(FenError::new(
+                FenField::Placement,
+                "Pawns Can't Be On The First Or Last Rank",
+            ));
+        }
+
+        // there's no castling in Antichess, whatever the FEN says
+        let castling = match variant {
+            Variant::Antichess => "-",
+            _ => fen.split_whitespace().nth(2).unwrap_or("-"),
+        };
+        for c in castling.chars().filter(|c| *c != '-') {
+            let color = if c.is_ascii_uppercase() { 0 } else { 1 };
+            let rank = back_rank(color);
+            let rook = Some(ROOK + color * 6);
+
+            let king_f = (0..8)
+                .find(|f| board.squares[square(*f, rank)] == Some(KING + color * 6))
+                .ok_or_else(|| {
+                    FenError::new(
+                        FenField::Castling,
+                        format!("'{c}' Needs The King On Its First Rank"),
+                    )
+                })?;
+
+            let rook_f = match c.to_ascii_lowercase() {
+                'k' => (king_f + 1..8)
+                    .rev()
+                    .find(|f| board.squares[square(*f, rank)] == rook),
+                'q' => (0..king_f).find(|f| board.squares[square(*f, rank)] == rook),
+                f => Some((f as u8 - b'a') as usize)
+                    .filter(|f| *f != king_f && board.squares[square(*f, rank)] == rook),
+            }
+            .ok_or_else(|| {
+                FenError::new(
+                    FenField::Castling,
+                    format!("'{c}' Needs A Rook On That Side Of The King"),
+                )
+            })?;
+
+            if !chess960 && (king_f != 4 || (rook_f != 0 && rook_f != 7)) {
+                return Err(FenError::new(
+                    FenField::Castling,
+                    format!("'{c}' Needs The King And Rook On Their Starting Squares"),
+                ));
+            }
+
+            let side = if rook_f > king_f { KINGSIDE } else { QUEENSIDE };
+            board.castling[color][side] = Some(rook_f);
+        }
+
+        if let Some(sq) = board.en_passant {
+            // the pawn that just made a double step, the square it passed, and where it came from
+            let (pawn, pawn_r, passed_r, from_r) = if board.white_to_move {
+                (PAWN + 6, 4, 5, 6)
+            } else {
+                (PAWN, 3, 2, 1)
+            };
+            let f = file_of(sq);
+
+            if rank_of(sq) != passed_r
+                || board.squares[square(f, pawn_r)] != Some(pawn)
+                || board.squares[square(f, passed_r)].is_some()
+                || board.squares[square(f, from_r)].is_some()
+            {
+                return Err(FenError::new(
+                    FenField::EnPassant,
+                    "No Pawn Could Have Just Passed Over It",
+                ));
+            }
+        }
+
+        if board.fullmoves == 0 {
+            return Err(FenError::new(FenField::FullmoveNumber, "It Starts At 1"));
+        }
+
+        if board.in_check(1 - board.side()) {
+            return Err(FenError::new(
+                FenField::Position,
+                format!(
+                    "{} Is In Check But It's Not Their Move",
+                    if board.white_to_move {
+                        "Black"
+                    } else {
+                        "White"
+                    }
+                ),
+            ));
+        }
+
+        Ok(board)
+    }
+
+    /// the usual start position
+    pub fn start() -> Self {
+        Self::from_fen(pos::START_FEN, Variant::Standard, false).unwrap()
+    }
+
+    /// castling rights are written as KQkq when that's the outermost rook, and by the rook's
+    /// file otherwise
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for r in (0..8).rev() {
+            let mut empty = 0;
+
+            for f in 0..8 {
+                match self.squares[square(f, r)] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen += &empty.to_string();
+                            empty = 0;
+                        }
+                        fen.push(PIECE_CHARS[piece]);
+
+                        if self.promoted & (1 << square(f, r)) != 0 {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                fen += &empty.to_string();
+            }
+
+            if r > 0 {
+                fen.push('/');
+            }
+        }
+
+        if self.has_pockets() {
+            fen.push('[');
+            for color in 0..2 {
+                for kind in (PAWN..KING).rev() {
+                    for _ in 0..self.pockets[color][kind] {
+                        fen.push(PIECE_CHARS[kind + color * 6]);
+                    }
+                }
+            }
+            fen.push(']');
+        }
+
+        fen += if self.white_to_move { " w " } else { " b " };
+
+        let mut castling = String::new();
+        for color in 0..2 {
+            for side in [KINGSIDE, QUEENSIDE] {
+                let Some(rook_f) = self.castling[color][side] else {
+                    continue;
+                };
+
+                let rank = back_rank(color);
+                let rook = Some(ROOK + color * 6);
+                let further_out = if side == KINGSIDE {
+                    (rook_f + 1..8).collect::<Vec<usize>>()
+                } else {
+                    (0..rook_f).collect()
+                };
+
+                let c = if further_out
+                    .iter()
+                    .any(|f| self.squares[square(*f, rank)] == rook)
+                {
+                    (rook_f as u8 + b'a') as char
+                } else if side == KINGSIDE {
+                    'k'
+                } else {
+                    'q'
+                };
+
+                castling.push(if color == 0 {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                });
+            }
+        }
+
+        fen += if castling.is_empty() { "-" } else { &castling };
+
+        fen += &match self.en_passant {
+            Some(sq) => format!(" {} ", to_algn(sq)),
+            None => " - ".to_string(),
+        };
+
+        fen + &format!("{} {}", self.halfmoves, self.fullmoves)
+    }
+
+    /// 0 for white, 1 for black
+    pub fn side(&self) -> usize {
+        if self.white_to_move { 0 } else { 1 }
+    }
+
+    pub fn side_to_move(&self) -> chess_color::Color {
+        if self.white_to_move {
+            chess_color::WHITE
+        } else {
+            chess_color::BLACK
+        }
+    }
+
+    pub fn has_pockets(&self) -> bool {
+        self.variant == Variant::Crazyhouse
+    }
+
+    fn count(&self, piece: usize) -> usize {
+        self.squares.iter().filter(|p| **p == Some(piece)).count()
+    }
+
+    pub fn king(&self, color: usize) -> Option<Square> {
+        (0..64).find(|sq| self.squares[*sq] == Some(KING + color * 6))
+    }
+
+    fn is_own(&self, sq: Square, color: usize) -> bool {
+        self.squares[sq].is_some_and(|p| p / 6 == color)
+    }
+
+    /// whether a piece of `by` could take on `sq`
+    fn attacked(&self, sq: Square, by: usize) -> bool {
+        let is = |sq: Option<Square>, kinds: &[usize]| {
+            sq.and_then(|sq| self.squares[sq])
+                .is_some_and(|p| p / 6 == by && kinds.contains(&(p % 6)))
+        };
+
+        // pawns take forward, so look backward from `sq`
+        let back = if by == 0 { -1 } else { 1 };
+        if [-1, 1]
+            .into_iter()
+            .any(|df| is(offset(sq, (df, back)), &[PAWN]))
+        {
+            return true;
+        }
+
+        if KNIGHT_STEPS.iter().any(|s| is(offset(sq, *s), &[KNIGHT]))
+            || KING_STEPS.iter().any(|s| is(offset(sq, *s), &[KING]))
+        {
+            return true;
+        }
+
+        for (directions, kinds) in [
+            (BISHOP_DIRECTIONS, [BISHOP, QUEEN]),
+            (ROOK_DIRECTIONS, [ROOK, QUEEN]),
+        ] {
+            for direction in directions {
+                let mut current = offset(sq, direction);
+
+                while let Some(s) = current {
+                    if self.squares[s].is_some() {
+                        if is(Some(s), &kinds) {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = offset(s, direction);
+                }
+            }
+        }
+
+        false
+    }
+
+    /// never in Antichess, where the king can be taken like anything else
+    pub fn in_check(&self, color: usize) -> bool {
+        self.variant != Variant::Antichess
+            && self
+                .king(color)
+                .is_some_and(|king| self.attacked(king, 1 - color))
+    }
+
+    /// moves that follow how the pieces move, the king may be left in check
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let us = self.side();
+        let mut moves = Vec::new();
+
+        for from in 0..64 {
+            let Some(piece) = self.squares[from].filter(|p| p / 6 == us) else {
+                continue;
+            };
+
+            match piece % 6 {
+                PAWN => self.pawn_moves(from, &mut moves),
+                KNIGHT => self.step_moves(from, &KNIGHT_STEPS, &mut moves),
+                BISHOP => self.slide_moves(from, &BISHOP_DIRECTIONS, &mut moves),
+                ROOK => self.slide_moves(from, &ROOK_DIRECTIONS, &mut moves),
+                QUEEN => {
+                    self.slide_moves(from, &BISHOP_DIRECTIONS, &mut moves);
+                    self.slide_moves(from, &ROOK_DIRECTIONS, &mut moves);
+                }
+                _ => self.step_moves(from, &KING_STEPS, &mut moves),
+            }
+        }
+
+        self.castling_moves(&mut moves);
+
+        if self.has_pockets() {
+            self.drop_moves(&mut moves);
+        }
+
+        moves
+    }
+
+    fn pawn_moves(&self, from: Square, moves: &mut Vec<Move>) {
+        let us = self.side();
+        let forward = if us == 0 { 1 } else { -1 };
+        // the horde's pawns on the first rank can step two squares as well
+        let double_step_ranks = match (us, self.variant) {
+            (0, Variant::Horde) => [0, 1],
+            (0, _) => [1, 1],
+            _ => [6, 6],
+        };
+        let promotions: &[usize] = match self.variant {
+            Variant::Antichess => &[QUEEN, ROOK, BISHOP, KNIGHT, KING],
+            _ => &[QUEEN, ROOK, BISHOP, KNIGHT],
+        };
+
+        let mut push = |to: Square| {
+            if rank_of(to) == back_rank(1 - us) {
+                for kind in promotions.iter().copied() {
+                    moves.push(Move::Normal {
+                        from,
+                        to,
+                        promotion: Some(kind),
+                    });
+                }
+            } else {
+                moves.push(Move::Normal {
+                    from,
+                    to,
+                    promotion: None,
+                });
+            }
+        };
+
+        if let Some(to) = offset(from, (0, forward)).filter(|to| self.squares[*to].is_none()) {
+            push(to);
+
+            if double_step_ranks.contains(&rank_of(from))
+                && let Some(to) = offset(to, (0, forward)).filter(|to| self.squares[*to].is_none())
+            {
+                push(to);
+            }
+        }
+
+        for df in [-1, 1] {
+            if let Some(to) = offset(from, (df, forward))
+                .filter(|to| self.is_own(*to, 1 - us) || self.en_passant == Some(*to))
+            {
+                push(to);
+            }
+        }
+    }
+
+    fn step_moves(&self, from: Square, steps: &[(isize, isize)], moves: &mut Vec<Move>) {
+        for step in steps {
+            if let Some(to) = offset(from, *step).filter(|to| !self.is_own(*to, self.side())) {
+                moves.push(Move::Normal {
+                    from,
+                    to,
+                    promotion: None,
+                });
+            }
+        }
+    }
+
+    fn slide_moves(&self, from: Square, directions: &[(isize, isize)], moves: &mut Vec<Move>) {
+        for direction in directions {
+            let mut current = offset(from, *direction);
+
+            while let Some(to) = current {
+                if self.is_own(to, self.side()) {
+                    break;
+                }
+
+                moves.push(Move::Normal {
+                    from,
+                    to,
+                    promotion: None,
+                });
+
+                if self.squares[to].is_some() {
+                    break;
+                }
+                current = offset(to, *direction);
+            }
+        }
+    }
+
+    /// anything in the pocket can go on any empty square, except pawns on the first or last rank
+    fn drop_moves(&self, moves: &mut Vec<Move>) {
+        let us = self.side();
+
+        for kind in (PAWN..KING).filter(|kind| self.pockets[us][*kind] > 0) {
+            for to in (0..64).filter(|to| self.squares[*to].is_none()) {
+                if kind != PAWN || ![0, 7].contains(&rank_of(to)) {
+                    moves.push(Move::Drop { kind, to });
+                }
+            }
+        }
+    }
+
+    /// every square the king and rook cross has to be empty apart from the two of them, and the
+    /// king can't be in check or pass through it
+    fn castling_moves(&self, moves: &mut Vec<Move>) {
+        let us = self.side();
+        let Some(king) = self.king(us) else {
+            return;
+        };
+
+        if self.attacked(king, 1 - us) {
+            return;
+        }
+
+        for rook_f in self.castling[us].into_iter().flatten() {
+            let rook = square(rook_f, rank_of(king));
+            let (king_to, rook_to) = castled_squares(king, rook);
+            let between = |a: Square, b: Square| a.min(b)..=a.max(b);
+
+            let clear = between(king, king_to)
+                .chain(between(rook, rook_to))
+                .all(|sq| sq == king || sq == rook || self.squares[sq].is_none());
+            let safe = between(king, king_to).all(|sq| !self.attacked(sq, 1 - us));
+
+            if clear && safe {
+                moves.push(Move::Castle { king, rook });
+            }
+        }
+    }
+
+    /// in Antichess nothing is ever in check, but a side that can take has to
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let us = self.side();
+        let moves = self
+            .pseudo_legal_moves()
+            .into_iter()
+            .filter(|mov| {
+                let mut after = self.clone();
+                after.make_move(*mov);
+                !after.in_check(us)
+            })
+            .collect::<Vec<Move>>();
+
+        if self.variant == Variant::Antichess && moves.iter().any(|m| self.is_capture(*m)) {
+            moves.into_iter().filter(|m| self.is_capture(*m)).collect()
+        } else {
+            moves
+        }
+    }
+
+    fn is_capture(&self, mov: Move) -> bool {
+        match mov {
+            Move::Normal { from, to, .. } => {
+                self.squares[to].is_some()
+                    || (self.squares[from].is_some_and(|p| p % 6 == PAWN)
+                        && file_of(from) != file_of(to))
+            }
+            Move::Castle { .. } | Move::Drop { .. } => false,
+        }
+    }
+
+    /// `mov` has to be legal
+    pub fn make_move(&mut self, mov: Move) {
+        let us = self.side();
+        let en_passant = self.en_passant.take();
+        let mut resets_halfmoves = false;
+
+        match mov {
+            Move::Normal {
+                from,
+                to,
+                promotion,
+            } => {
+                let piece = self.squares[from].unwrap();
+                let mut captured = self.squares[to];
+                let mut captured_on = to;
+
+                if piece % 6 == PAWN {
+                    resets_halfmoves = true;
+
+                    if Some(to) == en_passant && captured.is_none() {
+                        captured_on = square(file_of(to), rank_of(from));
+                        captured = self.squares[captured_on].take();
+                    }
+
+                    // a double step only leaves an en passant square if a pawn can take there, and
+                    // not for the horde's pawns stepping off the first rank
+                    if rank_of(from).abs_diff(rank_of(to)) == 2 && rank_of(from) == [1, 6][us] {
+                        let passed = square(file_of(from), (rank_of(from) + rank_of(to)) / 2);
+                        if [-1, 1].into_iter().any(|df| {
+                            offset(to, (df, 0))
+                                .is_some_and(|sq| self.squares[sq] == Some(PAWN + (1 - us) * 6))
+                        }) {
+                            self.en_passant = Some(passed);
+                        }
+                    }
+                }
+
+                if let Some(captured) = captured {
+                    resets_halfmoves = true;
+                    self.lose_castling_on(to);
+
+                    // a promoted piece goes back to being a pawn
+                    if self.has_pockets() {
+                        let kind = if self.promoted & (1 << captured_on) != 0 {
+                            PAWN
+                        } else {
+                            captured % 6
+                        };
+                        self.pockets[us][kind] += 1;
+                    }
+                }
+
+                if self.has_pockets() {
+                    let was_promoted = self.promoted & (1 << from) != 0;
+                    self.promoted &= !(1 << from | 1 << captured_on);
+
+                    if was_promoted || promotion.is_some() {
+                        self.promoted |= 1 << to;
+                    }
+                }
+
+                if piece % 6 == KING {
+                    self.castling[us] = [None; 2];
+                }
+                self.lose_castling_on(from);
+
+                self.squares[from] = None;
+                self.squares[to] = Some(promotion.map_or(piece, |kind| kind + us * 6));
+            }
+            Move::Castle { king, rook } => {
+                let (king_to, rook_to) = castled_squares(king, rook);
+                let (king_piece, rook_piece) = (self.squares[king], self.squares[rook]);
+
+                self.squares[king] = None;
+                self.squares[rook] = None;
+                self.squares[king_to] = king_piece;
+                self.squares[rook_to] = rook_piece;
+                self.castling[us] = [None; 2];
+            }
+            Move::Drop { kind, to } => {
+                self.squares[to] = Some(kind + us * 6);
+                self.pockets[us][kind] -= 1;
+            }
+        }
+
+        self.halfmoves = if resets_halfmoves {
+            0
+        } else {
+            self.halfmoves + 1
+        };
+
+        if !self.white_to_move {
+            self.fullmoves += 1;
+        }
+        self.white_to_move = !self.white_to_move;
+
+        if self.in_check(1 - us) {
+            self.checks[us] += 1;
+        }
+    }
+
+    /// a rook that moves or is taken can't castle anymore
+    fn lose_castling_on(&mut self, sq: Square) {
+        for color in 0..2 {
+            if rank_of(sq) == back_rank(color) {
+                for side in &mut self.castling[color] {
+                    if *side == Some(file_of(sq)) {
+                        *side = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// castling is the king taking its own rook in chess960, and the king's two square step
+    /// otherwise
+    pub fn to_uci(&self, mov: Move) -> String {
+        match mov {
+            Move::Normal {
+                from,
+                to,
+                promotion,
+            } => format!(
+                "{}{}{}",
+                to_algn(from),
+                to_algn(to),
+                promotion.map_or(String::new(), |kind| PIECE_CHARS[kind + 6].to_string())
+            ),
+            Move::Castle { king, rook } if self.chess960 => {
+                format!("{}{}", to_algn(king), to_algn(rook))
+            }
+            Move::Castle { king, .. } => format!("{}{}", to_algn(king), to_algn(mov.target())),
+            Move::Drop { kind, to } => format!("{}@{}", PIECE_CHARS[kind], to_algn(to)),
+        }
+    }
+
+    /// castling is accepted both as the king taking its own rook and as the king's two square
+    /// step
+    pub fn parse_uci(&self, uci: &str) -> Option<Move> {
+        let uci = uci.trim().to_ascii_lowercase();
+
+        self.legal_moves().into_iter().find(|mov| {
+            self.to_uci(*mov).to_ascii_lowercase() == uci
+                || matches!(mov, Move::Castle { king, rook }
+                    if format!("{}{}", to_algn(*king), to_algn(*rook)) == uci
+                        || format!("{}{}", to_algn(*king), to_algn(mov.target())) == uci)
+        })
+    }
+
+    pub fn to_san(&self, mov: Move) -> String {
+        let mut san = match mov {
+            Move::Castle { king, rook } => if rook > king { "O-O" } else { "O-O-O" }.to_string(),
+            Move::Drop { kind, to } => format!("{}@{}", PIECE_CHARS[kind], to_algn(to)),
+            Move::Normal {
+                from,
+                to,
+                promotion,
+            } => {
+                let piece = self.squares[from].unwrap_or_default();
+                let kind = piece % 6;
+                let is_capture = self.is_capture(mov);
+
+                let mut san = String::new();
+
+                if kind == PAWN {
+                    if is_capture {
+                        san.push((file_of(from) as u8 + b'a') as char);
+                    }
+                } else {
+                    san.push(PIECE_CHARS[kind]);
+
+                    let others = self
+                        .legal_moves()
+                        .into_iter()
+                        .filter_map(|m| match m {
+                            Move::Normal { from: f, to: t, .. }
+                                if t == to && f != from && self.squares[f] == Some(piece) =>
+                            {
+                                Some(f)
+                            }
+                            _ => None,
+                        })
+                        .collect::<Vec<Square>>();
+
+                    if !others.is_empty() {
+                        let square = to_algn(from);
+
+                        if others.iter().all(|f| file_of(*f) != file_of(from)) {
+                            san += &square[..1];
+                        } else if others.iter().all(|f| rank_of(*f) != rank_of(from)) {
+                            san += &square[1..];
+                        } else {
+                            san += &square;
+                        }
+                    }
+                }
+
+                if is_capture {
+                    san.push('x');
+                }
+
+                san += &to_algn(to);
+
+                if let Some(kind) = promotion {
+                    san.push('=');
+                    san.push(PIECE_CHARS[kind]);
+                }
+
+                san
+            }
+        };
+
+        let mut after = self.clone();
+        after.make_move(mov);
+
+        if after.in_check(after.side()) {
+            san.push(if after.legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+
+        san
+    }
+
+    /// reads a move typed as SAN ("Nf3", "exd5", "O-O") or UCI ("g1f3"), it has to be legal
+    pub fn parse_move(&self, text: &str) -> Option<Move> {
+        if let Some(mov) = self.parse_uci(text) {
+            return Some(mov);
+        }
+
+        let plain = |san: &str| {
+            san.trim()
+                .trim_end_matches(['+', '#', '!', '?'])
+                .replace('0', "O")
+        };
+        let text = plain(text);
+
+        // piece letters typed in lowercase are fine too, except for b which could be a pawn's file
+        let mut capitalized = text.clone();
+        if text.starts_with(['n', 'r', 'q', 'k']) {
+            capitalized[..1].make_ascii_uppercase();
+        }
+
+        self.legal_moves().into_iter().find(|mov| {
+            let san = plain(&self.to_san(*mov));
+            san == text || san == capitalized
+        })
+    }
+
+    /// converts an engine's principal variation to SAN, stopping at the first move that doesn't
+    /// fit the board (which happens when the line belongs to a search that has been replaced)
+    pub fn line_to_san(&self, line: &[String], max_moves: usize) -> String {
+        let mut board = self.clone();
+        let mut san = Vec::new();
+
+        for uci_move in line.iter().take(max_moves) {
+            let Some(mov) = board.parse_uci(uci_move) else {
+                break;
+            };
+
+            san.push(board.to_san(mov));
+            board.make_move(mov);
+        }
+
+        san.join(" ")
+    }
+
+    /// the legal move that takes the piece on `from` to `to`, a pawn reaching the last rank
+    /// becomes `promotion` or a queen, and the king castles by moving onto its own rook or to
+    /// the square castling puts it on
+    pub fn move_between(&self, from: Square, to: Square, promotion: Option<usize>) -> Option<Move> {
+        let legal = self.legal_moves();
+
+        legal
+            .iter()
+            .copied()
+            .find(|mov| {
+                *mov == Move::Normal {
+                    from,
+                    to,
+                    promotion: None,
+                } || *mov
+                    == Move::Normal {
+                        from,
+                        to,
+                        promotion: Some(promotion.unwrap_or(QUEEN)),
+                    }
+            })
+            .or_else(|| {
+                legal.into_iter().find(|mov| {
+                    matches!(mov, Move::Castle { king, rook }
+                        if *king == from && (*rook == to || mov.target() == to))
+                })
+            })
+    }
+
+    /// the legal move that drops a `kind` piece from the pocket onto `to`
+    pub fn drop_on(&self, kind: usize, to: Square) -> Option<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|mov| *mov == Move::Drop { kind, to })
+    }
+
+    /// whether `color` can't possibly mate, a king and at most one knight or bishop
+    pub fn insufficient_material(&self, color: usize) -> bool {
+        let mut minors = 0;
+
+        for piece in self.squares.iter().flatten().filter(|p| *p / 6 == color) {
+            match piece % 6 {
+                KNIGHT | BISHOP => minors += 1,
+                KING => {}
+                _ => return false,
+            }
+        }
+
+        minors <= 1
+    }
+
+    /// white's material minus black's, pockets included
+    pub fn material_diff(&self) -> i32 {
+        let pocketed = (0..2).flat_map(|color| {
+            (PAWN..KING).flat_map(move |kind| {
+                std::iter::repeat_n(kind + color * 6, self.pockets[color][kind] as usize)
+            })
+        });
+
+        self.squares
+            .iter()
+            .flatten()
+            .copied()
+            .chain(pocketed)
+            .map(|p| {
+                if p / 6 == 0 {
+                    PIECE_VALUES[p % 6]
+                } else {
+                    -PIECE_VALUES[p % 6]
+                }
+            })
+            .sum()
+    }
+
+    /// how the game ended by what's on the board, the variant's own goal or mate and stalemate
+    pub fn outcome(&self) -> Option<(&'static str, GameResult)> {
+        if let Some(outcome) = self.variant.outcome(self) {
+            return Some(outcome);
+        }
+
+        if !self.legal_moves().is_empty() {
+            None
+        } else if !self.in_check(self.side()) {
+            Some(("Draw By Stalemate", GameResult::Draw))
+        } else if self.white_to_move {
+            Some(("Black Wins By Checkmate", GameResult::BlackWins))
+        } else {
+            Some(("White Wins By Checkmate", GameResult::WhiteWins))
+        }
+    }
+
+    /// the same pieces on the same squares with the same rights, which is what repetition is
+    /// about
+    fn same_position(&self, other: &Board) -> bool {
+        self.squares == other.squares
+            && self.white_to_move == other.white_to_move
+            && self.castling == other.castling
+            && self.en_passant == other.en_passant
+            && self.pockets == other.pockets
+    }
+}
+
+/// takes the pockets and the `~` after promoted pieces out of a Crazyhouse FEN, so that
+/// `fen::parse` can read the rest
+fn split_pockets(fen: &str) -> Result<(String, [[u8; 5]; 2], u64), FenError> {
+    let mut fields = fen.split_whitespace();
+    let placement = fields.next().unwrap_or_default();
+
+    let (placement, pocket) = match placement.split_once('[') {
+        Some((placement, pocket)) => (
+            placement,
+            pocket.strip_suffix(']').ok_or_else(|| {
+                FenError::new(FenField::Placement, "The Pockets Are Missing A Closing ']'")
+            })?,
+        ),
+        None => (placement, ""),
+    };
+
+    let mut pockets = [[0; 5]; 2];
+    for c in pocket.chars() {
+        let piece = PIECE_CHARS
+            .iter()
+            .position(|p| *p == c)
+            .filter(|p| p % 6 != KING)
+            .ok_or_else(|| {
+                FenError::new(FenField::Placement, format!("'{c}' Can't Be In A Pocket"))
+            })?;
+        pockets[piece / 6][piece % 6] += 1;
+    }
+
+    let mut promoted = 0;
+    let (mut file, mut rank): (usize, usize) = (0, 7);
+    for c in placement.chars() {
+        match c {
+            '/' => {
+                rank = rank.saturating_sub(1);
+                file = 0;
+            }
+            '~' if (1..=8).contains(&file) => promoted |= 1 << square(file - 1, rank),
+            '~' => {
+                return Err(FenError::new(
+                    FenField::Placement,
+                    "'~' Has To Come After A Piece",
+                ));
+            }
+            c => file += c.to_digit(10).unwrap_or(1) as usize,
+        }
+    }
+
+    let fen = std::iter::once(placement.replace('~', ""))
+        .chain(fields.map(str::to_string))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    Ok((fen, pockets, promoted))
+}
+
+/// a game played by these rules, every board along the way is kept for going back through it
+#[derive(Clone)]
+pub struct History {
+    pub starting_fen: String,
+    pub moves: Vec<Move>,
+    pub boards: Vec<Board>, // the board before each move, then the current one
+}
+
+impl History {
+    pub fn new(board: Board) -> Self {
+        Self {
+            starting_fen: board.to_fen(),
+            moves: Vec::new(),
+            boards: vec![board],
+        }
+    }
+
+    pub fn current(&self) -> &Board {
+        self.boards.last().unwrap()
+    }
+
+    pub fn ply(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn push(&mut self, mov: Move) {
+        let mut board = self.current().clone();
+        board.make_move(mov);
+
+        self.moves.push(mov);
+        self.boards.push(board);
+    }
+
+    /// goes back to how the game was after `ply` moves
+    pub fn truncate(&mut self, ply: usize) {
+        self.moves.truncate(ply);
+        self.boards.truncate(ply + 1);
+    }
+
+    pub fn is_3_rep(&self) -> bool {
+        let current = self.current();
+        self.boards
+            .iter()
+            .filter(|b| b.same_position(current))
+            .count()
+            >= 3
+    }
+
+    /// how the game ended by the rules alone
+    pub fn ending(&self) -> Option<(&'static str, GameResult)> {
+        let board = self.current();
+
+        if let Some(outcome) = board.outcome() {
+            Some(outcome)
+        } else if self.is_3_rep() {
+            Some(("Draw By Three-Fold Repetition", GameResult::Draw))
+        } else if board.halfmoves >= FIFTY_MOVE_PLIES {
+            Some(("Draw By Fifty Move Rule", GameResult::Draw))
+        } else if board.variant.insufficient_material_draws()
+            && board.insufficient_material(0)
+            && board.insufficient_material(1)
+        {
+            Some(("Draw By Insufficient Material", GameResult::Draw))
+        } else {
+            None
+        }
+    }
+
+    pub fn uci_moves(&self) -> Vec<String> {
+        self.moves
+            .iter()
+            .zip(&self.boards)
+            .map(|(mov, board)| board.to_uci(*mov))
+            .collect()
+    }
+
+    pub fn sans(&self) -> Vec<String> {
+        self.moves
+            .iter()
+            .zip(&self.boards)
+            .map(|(mov, board)| board.to_san(*mov))
+            .collect()
+    }
+
+    /// the uci command that sets up the game so far
+    pub fn position_cmd(&self) -> String {
+        self.position_cmd_at(self.ply())
+    }
+
+    /// the uci command that sets up the game as it was after `ply` moves
+    pub fn position_cmd_at(&self, ply: usize) -> String {
+        if ply == 0 {
+            format!("position fen {}", self.starting_fen)
+        } else {
+            format!(
+                "position fen {} moves {}",
+                self.starting_fen,
+                self.uci_moves()[..ply].join(" ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perft(board: &Board, depth: usize) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+
+        board
+            .legal_moves()
+            .into_iter()
+            .map(|mov| {
+                let mut after = board.clone();
+                after.make_move(mov);
+                perft(&after, depth - 1)
+            })
+            .sum()
+    }
+
+    fn assert_perft(fen: &str, variant: Variant, chess960: bool, counts: &[usize]) {
+        let board = Board::from_fen(fen, variant, chess960).unwrap();
+
+        for (depth, count) in counts.iter().enumerate() {
+            assert_eq!(perft(&board, depth + 1), *count, "depth {}", depth + 1);
+        }
+    }
+
+    #[test]
+    fn perft_standard() {
+        assert_perft(pos::START_FEN, Variant::Standard, false, &[20, 400, 8902]);
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            Variant::Standard,
+            false,
+            &[48, 2039, 97862],
+        );
+    }
+
+    #[test]
+    fn perft_chess960() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            Variant::Standard,
+            true,
+            &[21, 528, 12189],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            Variant::Standard,
+            true,
+            &[21, 807, 18002],
+        );
+    }
+
+    #[test]
+    fn perft_horde() {
+        assert_perft(
+            Variant::Horde.start_fen(),
+            Variant::Horde,
+            false,
+            &[8, 128, 1274, 23310],
+        );
+    }
+
+    #[test]
+    fn perft_antichess() {
+        assert_perft(pos::START_FEN, Variant::Antichess, false, &[20, 400, 8067]);
+    }
+
+    #[test]
+    fn perft_crazyhouse() {
+        assert_perft(
+            pos::START_FEN,
+            Variant::Crazyhouse,
+            false,
+            &[20, 400, 8902, 197281],
+        );
+    }
+
+    #[test]
+    fn crazyhouse_pockets_and_drops() {
+        let board = Board::from_fen(
+            "4k3/8/8/8/8/8/8/4K3[Nn] w - - 0 1",
+            Variant::Crazyhouse,
+            false,
+        )
+        .unwrap();
+
+        // five king moves and a knight on any of the 62 empty squares
+        assert_eq!(board.legal_moves().len(), 67);
+
+        let drop = board.parse_uci("N@f3").unwrap();
+        assert_eq!(
+            drop,
+            Move::Drop {
+                kind: KNIGHT,
+                to: square(5, 2)
+            }
+        );
+        assert_eq!(board.to_san(drop), "N@f3");
+
+        let mut after = board.clone();
+        after.make_move(drop);
+        assert_eq!(after.to_fen(), "4k3/8/8/8/8/5N2/8/4K3[n] b - - 1 1");
+    }
+
+    #[test]
+    fn crazyhouse_promoted_pieces_are_pocketed_as_pawns() {
+        let fen = "4k3/8/8/8/8/8/8/R2q~K3[] w - - 0 1";
+        let board = Board::from_fen(fen, Variant::Crazyhouse, false).unwrap();
+        assert_eq!(board.to_fen(), fen);
+
+        let mut after = board.clone();
+        after.make_move(board.parse_uci("a1d1").unwrap());
+        assert_eq!(after.to_fen(), "4k3/8/8/8/8/8/8/3RK3[P] b - - 0 1");
+    }
+
+    #[test]
+    fn antichess_is_won_by_losing_every_piece() {
+        let board =
+            Board::from_fen("8/8/8/8/8/8/1p6/8 w - - 0 1", Variant::Antichess, false).unwrap();
+
+        assert_eq!(
+            board.outcome().map(|(message, _)| message),
+            Some("White Wins By Losing Every Piece")
+        );
+    }
+
+    #[test]
+    fn chess960_castling_is_the_king_taking_its_rook() {
+        // king on b1 and rooks on a1 and h1, castling queenside leaves the king on c1
+        let board = Board::from_fen(
+            "rk5r/pppppppp/8/8/8/8/PPPPPPPP/RK5R w KQkq - 0 1",
+            Variant::Standard,
+            true,
+        )
+        .unwrap();
+
+        let queenside = board.parse_uci("b1a1").unwrap();
+        assert_eq!(
+            queenside,
+            Move::Castle {
+                king: square(1, 0),
+                rook: square(0, 0)
+            }
+        );
+        assert_eq!(board.to_san(queenside), "O-O-O");
+
+        let mut after = board.clone();
+        after.make_move(queenside);
+        assert_eq!(
+            after.to_fen(),
+            "rk5r/pppppppp/8/8/8/8/PPPPPPPP/2KR3R b kq - 1 1"
+        );
+        assert_eq!(
+            Board::from_fen(&after.to_fen(), Variant::Standard, true)
+                .unwrap()
+                .to_fen(),
+            after.to_fen()
+        );
+    }
+
+    #[test]
+    fn shredder_castling_names_inner_rooks() {
+        let board = Board::from_fen(
+            "1r2k1r1/8/8/8/8/8/8/RR2K2R w BHg - 0 1",
+            Variant::Standard,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(board.to_fen(), "1r2k1r1/8/8/8/8/8/8/RR2K2R w KBk - 0 1");
+    }
+}
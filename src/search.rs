@@ -0,0 +1,183 @@
+// finding positions in the game database, by the exact position, the material on the board, or
+// a pattern of pieces that all have to be on their squares
+//
+// exact positions are compared by their Zobrist keys from libchess, so the castling and en passant
+// rights count as well as where the pieces are and who's to move. libchess only plays standard
+// chess, so exact searches leave Chess960 and variant games out, the other two look through all
+
+use libchess::{self as lc, pos};
+
+use crate::{board_editor::SetupPosition, database, rules, variant::Variant};
+
+pub const KINDS: [&str; 3] = ["Exact Position", "Material", "Pattern"];
+
+// same order as the piece textures and `SetupPosition::board`
+const PIECE_LETTERS: [char; 6] = ['P', 'N', 'B', 'R', 'Q', 'K'];
+
+fn zobrist_key(fen: &str, zb: &lc::ZobristValues) -> u64 {
+    pos::Position::from_fen(fen, zb).key()
+}
+
+/// how many of each piece there are, indexed like the piece textures
+fn material(board: &rules::Board) -> [u8; 12] {
+    let mut counts = [0; 12];
+
+    for piece in board.squares.iter().flatten() {
+        counts[*piece] += 1;
+    }
+
+    counts
+}
+
+/// reads signatures like "KRPvKR" (white's pieces, then black's) or "KRPkr" (white in uppercase)
+pub fn parse_material(text: &str) -> Option<[u8; 12]> {
+    let text = text.trim();
+    let mut counts = [0; 12];
+
+    let mut add = |c: char, black: bool| -> Option<()> {
+        let kind = PIECE_LETTERS
+            .iter()
+            .position(|l| *l == c.to_ascii_uppercase())?;
+        counts[kind + if black { 6 } else { 0 }] += 1;
+        Some(())
+    };
+
+    if let Some((white, black)) = text.split_once(['v', 'V']) {
+        for c in white.trim().chars() {
+            add(c, false)?;
+        }
+        for c in black.trim().chars() {
+            add(c, true)?;
+        }
+    } else {
+        for c in text.chars() {
+            add(c, c.is_ascii_lowercase())?;
+        }
+    }
+
+    (counts != [0; 12]).then_some(counts)
+}
+
+pub enum Query {
+    Exact(u64), // the position's Zobrist key
+    Material([u8; 12]),
+    Pattern(Box<SetupPosition>),
+}
+
+impl Query {
+    /// an exact search for `setup`, or a search for its pieces wherever the rest are
+    pub fn from_setup(setup: &SetupPosition, exact: bool, zb: &lc::ZobristValues) -> Self {
+        if exact {
+            Query::Exact(zobrist_key(&setup.to_fen(), zb))
+        } else {
+            Query::Pattern(Box::new(setup.clone()))
+        }
+    }
+
+    fn matches(&self, board: &rules::Board, zb: &lc::ZobristValues) -> bool {
+        match self {
+            Query::Exact(key) => {
+                board.variant == Variant::Standard
+                    && !board.chess960
+                    && zobrist_key(&board.to_fen(), zb) == *key
+            }
+            Query::Material(counts) => material(board) == *counts,
+            Query::Pattern(setup) => (0..8).all(|r| {
+                (0..8).all(|f| {
+                    setup.piece_on(f, r).is_none_or(|piece| {
+                        board.squares[rules::square(f as usize, r as usize)] == Some(piece)
+                    })
+                })
+            }),
+        }
+    }
+}
+
+/// the games among `candidates` (indices into `entries`) that reach a position matching `query`,
+/// along with the first ply they reach it at
+pub fn search(
+    entries: &[database::Entry],
+    candidates: &[usize],
+    query: &Query,
+    zb: &lc::ZobristValues,
+) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+
+    for idx in candidates.iter().copied() {
+        let game = &entries[idx].game;
+        let mut board = game.start.clone();
+
+        for ply in 0..=game.main_line.len() {
+            if query.matches(&board, zb) {
+                found.push((idx, ply));
+                break;
+            }
+
+            if let Some(mov) = game.main_line.get(ply) {
+                board.make_move(*mov);
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn;
+
+    #[test]
+    fn material_with_and_without_v() {
+        // K, R and P for white, K and R for black
+        let mut expected = [0; 12];
+        expected[5] = 1;
+        expected[3] = 1;
+        expected[0] = 1;
+        expected[11] = 1;
+        expected[9] = 1;
+
+        assert_eq!(parse_material("KRPvKR"), Some(expected));
+        assert_eq!(parse_material(" krp V kr "), Some(expected));
+        assert_eq!(parse_material("KRPkr"), Some(expected));
+    }
+
+    #[test]
+    fn pieces_are_counted() {
+        let counts = parse_material("KPPPvKNN").unwrap();
+
+        assert_eq!((counts[0], counts[5], counts[7], counts[11]), (3, 1, 2, 1));
+    }
+
+    #[test]
+    fn bad_material_is_rejected() {
+        assert_eq!(parse_material("KXvK"), None);
+        assert_eq!(parse_material(""), None);
+        assert_eq!(parse_material("v"), None);
+    }
+
+    #[test]
+    fn patterns_are_found_at_their_first_ply() {
+        let entries = pgn::read("1. e4 e5 2. Nf3 Nc6 *\n\n1. d4 d5 *\n")
+            .unwrap()
+            .into_iter()
+            .map(|game| database::Entry {
+                game,
+                sans: String::new(),
+                opening: None,
+            })
+            .collect::<Vec<database::Entry>>();
+
+        // a white knight on f3 and a black pawn on e5
+        let mut setup = SetupPosition::empty();
+        setup.set_piece(5, 2, Some(1));
+        setup.set_piece(4, 4, Some(6));
+
+        let zb = lc::init().zb;
+        let query = Query::from_setup(&setup, false, &zb);
+        assert_eq!(search(&entries, &[0, 1], &query, &zb), vec![(0, 3)]);
+
+        // only the games let through are searched
+        assert!(search(&entries, &[1], &query, &zb).is_empty());
+    }
+}
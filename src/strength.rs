@@ -0,0 +1,84 @@
+// playing engines below full strength, through their own options when they have them, or by
+// sometimes picking a worse line from a multipv search when they don't
+
+use std::time;
+
+use crate::{
+    engine_info::{EngineOption, Score},
+    review,
+};
+
+// the range stockfish's UCI_Elo covers, also used to spread an elo over "Skill Level"
+pub const WEAKEST_ELO: u32 = 1350;
+pub const STRONGEST_ELO: u32 = 2850;
+
+const EMULATION_MULTIPV: i32 = 4;
+// how many elo below full strength it takes to allow one more percent of win chance to be lost
+const ELO_PER_WIN_PERCENT: f32 = 50.0;
+
+/// the options to send for `elo`, and whether chess_tail has to weaken the engine itself by
+/// picking among its lines
+pub fn setup_commands(options: &[EngineOption], elo: u32) -> (Vec<String>, bool) {
+    let find = |name: &str| options.iter().find(|o| o.name.eq_ignore_ascii_case(name));
+
+    if let (Some(_), Some(elo_option)) = (find("UCI_LimitStrength"), find("UCI_Elo")) {
+        let elo = (elo as i32).clamp(
+            elo_option.min.unwrap_or(i32::MIN),
+            elo_option.max.unwrap_or(i32::MAX),
+        );
+
+        return (
+            vec![
+                "setoption name UCI_LimitStrength value true".to_string(),
+                format!("setoption name UCI_Elo value {elo}"),
+            ],
+            false,
+        );
+    }
+
+    if let Some(skill) = find("Skill Level") {
+        let (min, max) = (skill.min.unwrap_or(0), skill.max.unwrap_or(20));
+        let fraction = (elo.clamp(WEAKEST_ELO, STRONGEST_ELO) - WEAKEST_ELO) as f32
+            / (STRONGEST_ELO - WEAKEST_ELO) as f32;
+        let level = min + ((max - min) as f32 * fraction).round() as i32;
+
+        return (
+            vec![format!("setoption name Skill Level value {level}")],
+            false,
+        );
+    }
+
+    if let Some(multipv) = find("MultiPV") {
+        let lines = EMULATION_MULTIPV.min(multipv.max.unwrap_or(EMULATION_MULTIPV));
+        return (vec![format!("setoption name MultiPV value {lines}")], true);
+    }
+
+    (Vec::new(), false) // nothing to weaken it with, so it plays at full strength
+}
+
+/// picks a random move among the first moves of `lines` that don't lose more winning chances than
+/// `elo` allows, `lines` holds the score and first move of every multipv line
+pub fn pick_move(lines: &[Option<(Score, String)>], elo: u32) -> Option<String> {
+    let lines = lines.iter().flatten().collect::<Vec<&(Score, String)>>();
+    let best = lines
+        .iter()
+        .map(|(score, _)| review::win_percent(*score))
+        .fold(f32::MIN, f32::max);
+    let allowed_loss = STRONGEST_ELO.saturating_sub(elo) as f32 / ELO_PER_WIN_PERCENT;
+
+    let candidates = lines
+        .into_iter()
+        .filter(|(score, _)| best - review::win_percent(*score) <= allowed_loss)
+        .collect::<Vec<&(Score, String)>>();
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let nanos = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as usize;
+
+    Some(candidates[nanos % candidates.len()].1.clone())
+}
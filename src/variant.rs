@@ -0,0 +1,133 @@
+// rules that change how a game is won, or how the pieces move, on top of standard chess
+
+use libchess::pos;
+
+use crate::{
+    game::GameResult,
+    rules::{self, Board},
+};
+
+const HORDE_START_FEN: &str =
+    "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+
+// d4, e4, d5, e5
+const HILL: [rules::Square; 4] = [27, 28, 35, 36];
+const CHECKS_TO_WIN: u32 = 3;
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum Variant {
+    #[default]
+    Standard,
+    KingOfTheHill,
+    ThreeCheck,
+    Horde,
+    Antichess,
+    Crazyhouse,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 6] = [
+        Variant::Standard,
+        Variant::KingOfTheHill,
+        Variant::ThreeCheck,
+        Variant::Horde,
+        Variant::Antichess,
+        Variant::Crazyhouse,
+    ];
+
+    pub fn to_display_fmt(self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::ThreeCheck => "Three-check",
+            Variant::Horde => "Horde",
+            Variant::Antichess => "Antichess",
+            Variant::Crazyhouse => "Crazyhouse",
+        }
+    }
+
+    /// the value engines expect for the UCI_Variant option
+    pub fn to_uci_fmt(self) -> Option<&'static str> {
+        match self {
+            Variant::Standard => None,
+            Variant::KingOfTheHill => Some("kingofthehill"),
+            Variant::ThreeCheck => Some("3check"),
+            Variant::Horde => Some("horde"),
+            Variant::Antichess => Some("antichess"),
+            Variant::Crazyhouse => Some("crazyhouse"),
+        }
+    }
+
+    pub fn start_fen(self) -> &'static str {
+        match self {
+            Variant::Horde => HORDE_START_FEN,
+            _ => pos::START_FEN,
+        }
+    }
+
+    /// the king can still walk to the hill with nothing else left, so bare kings aren't a draw,
+    /// the goal of Horde and Antichess has nothing to do with mating, and in Crazyhouse whatever
+    /// was taken can come back
+    pub fn insufficient_material_draws(self) -> bool {
+        matches!(self, Variant::Standard | Variant::ThreeCheck)
+    }
+
+    /// how the game ended if the variant's own goal ended it, mate and stalemate are up to
+    /// `Board::outcome`
+    pub fn outcome(self, board: &Board) -> Option<(&'static str, GameResult)> {
+        let us = board.side();
+        let has_pieces = |color: usize| board.squares.iter().flatten().any(|p| p / 6 == color);
+        let us_win = if us == 0 {
+            GameResult::WhiteWins
+        } else {
+            GameResult::BlackWins
+        };
+
+        match self {
+            Variant::Standard | Variant::Crazyhouse => None,
+            Variant::KingOfTheHill => {
+                let on_hill = |color| board.king(color).is_some_and(|sq| HILL.contains(&sq));
+
+                if on_hill(0) {
+                    Some(("White Wins By King Of The Hill", GameResult::WhiteWins))
+                } else if on_hill(1) {
+                    Some(("Black Wins By King Of The Hill", GameResult::BlackWins))
+                } else {
+                    None
+                }
+            }
+            Variant::ThreeCheck => {
+                if board.checks[0] >= CHECKS_TO_WIN {
+                    Some(("White Wins By Three Checks", GameResult::WhiteWins))
+                } else if board.checks[1] >= CHECKS_TO_WIN {
+                    Some(("Black Wins By Three Checks", GameResult::BlackWins))
+                } else {
+                    None
+                }
+            }
+            Variant::Horde if !has_pieces(0) => {
+                Some(("Black Wins By Destroying The Horde", GameResult::BlackWins))
+            }
+            Variant::Horde => None,
+            // whoever can't move wins, mostly by having nothing left to move
+            Variant::Antichess => {
+                if !has_pieces(us) {
+                    Some((
+                        [
+                            "White Wins By Losing Every Piece",
+                            "Black Wins By Losing Every Piece",
+                        ][us],
+                        us_win,
+                    ))
+                } else if board.legal_moves().is_empty() {
+                    Some((
+                        ["White Wins By Stalemate", "Black Wins By Stalemate"][us],
+                        us_win,
+                    ))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
@@ -2,18 +2,79 @@
 
 use std::{collections::HashSet, fs, time};
 
-use libchess::{
-    color as chess_color, moves,
-    piece::{self, bb},
-    pos,
-};
+use libchess::pos;
 
 use macroquad::prelude::*;
 
-use crate::app;
+use crate::{app, board_editor, rules};
+
+pub type Arrow = (pos::File, pos::Rank, pos::File, pos::Rank);
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ArrowStyle {
+    pub color: Color,
+    pub thickness: f32, // relative to the square size
+}
+
+pub const USER_ARROW: ArrowStyle = ArrowStyle {
+    color: Color::new(1.0, 0.63, 0.0, 0.7),
+    thickness: 1.0 / 3.0,
+};
+
+pub const BEST_MOVE_ARROW: ArrowStyle = ArrowStyle {
+    color: Color::new(0.2, 0.5, 1.0, 0.8),
+    thickness: 1.0 / 4.0,
+};
+
+pub const SECOND_LINE_ARROW: ArrowStyle = ArrowStyle {
+    color: Color::new(0.3, 0.8, 0.3, 0.7),
+    thickness: 1.0 / 5.0,
+};
+
+const LEGAL_MOVE_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.3);
+
+/// how pieces are shown, everything but `Normal` is for blindfold training
+#[derive(Clone, Copy, PartialEq)]
+pub enum PieceDisplay {
+    Normal,
+    Hidden,
+    OnlyWhite,
+    OnlyBlack,
+    Discs, // every piece looks the same, only where they are is shown
+}
+
+impl PieceDisplay {
+    pub const ALL: [PieceDisplay; 5] = [
+        PieceDisplay::Normal,
+        PieceDisplay::Hidden,
+        PieceDisplay::OnlyWhite,
+        PieceDisplay::OnlyBlack,
+        PieceDisplay::Discs,
+    ];
+
+    pub fn to_display_fmt(self) -> &'static str {
+        match self {
+            PieceDisplay::Normal => "Normal",
+            PieceDisplay::Hidden => "Hidden",
+            PieceDisplay::OnlyWhite => "Only White",
+            PieceDisplay::OnlyBlack => "Only Black",
+            PieceDisplay::Discs => "Discs",
+        }
+    }
+
+    /// texture indices for white pieces come first
+    fn shows(&self, texture_idx: usize) -> bool {
+        match self {
+            PieceDisplay::Hidden => false,
+            PieceDisplay::OnlyWhite => texture_idx < 6,
+            PieceDisplay::OnlyBlack => texture_idx >= 6,
+            PieceDisplay::Normal | PieceDisplay::Discs => true,
+        }
+    }
+}
 
 pub struct VisualPiece {
-    pub current_square: pos::Square,
+    pub current_square: rules::Square,
     pub current_pos: Vec2,
     pub desired_pos: Vec2,
     pub texture_idx: usize,
@@ -26,15 +87,20 @@ pub struct VisualBoard {
     pub is_flipped: bool,
     pub square_size: f32,
     pub pos: Vec2,
-    pub eval_bar_width: f32,
+    pub eval_bar_width: f32, // the room left of the board, where Crazyhouse pockets go
     pub mouse_input_sqs: app::MouseInputSquares,
     pub draw_ply: usize,
-    pub selected_piece: piece::Piece,
+    pub selected_piece: Option<usize>, // texture index
+    pub click_deselects: bool, // the selected piece was clicked again, so let go of it on release
     pub pieces: Vec<VisualPiece>,
     pub selected_piece_theme: usize,
     pub selected_board_theme: usize,
+    pub piece_display: PieceDisplay,
     pub selected_squares: HashSet<(pos::File, pos::Rank)>,
-    pub arrows: HashSet<(pos::File, pos::Rank, pos::File, pos::Rank)>,
+    pub arrows: HashSet<Arrow>,
+    pub engine_arrows: Vec<(Arrow, ArrowStyle)>, // drawn below the user's arrows
+    pub premoves: Vec<(rules::Square, rules::Square, usize)>, // played in order once it's the user's turn
+    pub premove_promotion: usize, // the kind of piece, a queen until changed
     board_textures: Vec<Texture2D>,
     piece_textures: Vec<[Texture2D; 12]>,
 }
@@ -56,20 +122,51 @@ impl VisualBoard {
                 up_left: None,
                 down_left: None,
                 down_right: None,
+                held_drop: None,
             },
             draw_ply: 0,
-            selected_piece: piece::NONE,
+            selected_piece: None,
+            click_deselects: false,
             pieces: Vec::new(),
             selected_squares: HashSet::new(),
             arrows: HashSet::new(),
+            engine_arrows: Vec::new(),
+            premoves: Vec::new(),
+            premove_promotion: rules::QUEEN,
             selected_piece_theme: 0,
             selected_board_theme: 0,
+            piece_display: PieceDisplay::Normal,
         }
     }
 
     pub fn board_width(&self) -> f32 {
         self.square_size * 8.0
     }
+
+    pub fn square_at(&self, point: Vec2) -> Option<(pos::File, pos::Rank)> {
+        let col = ((point.x - self.pos.x) / self.square_size).floor();
+        let row = ((point.y - self.pos.y) / self.square_size).floor();
+
+        if !(0.0..8.0).contains(&col) || !(0.0..8.0).contains(&row) {
+            return None;
+        }
+
+        let (col, row) = (col as isize, row as isize);
+
+        Some(if self.is_flipped {
+            (7 - col, row)
+        } else {
+            (col, 7 - row)
+        })
+    }
+
+    pub fn square_pos(&self, file: pos::File, rank: pos::Rank) -> Vec2 {
+        self.pos
+            + vec2(
+                (file - self.is_flipped as isize * 7).abs() as f32 * self.square_size,
+                (rank - !self.is_flipped as isize * 7).abs() as f32 * self.square_size,
+            )
+    }
 }
 
 impl VisualBoard {
@@ -130,75 +227,99 @@ impl VisualBoard {
         }
     }
 
-    pub fn sync_pieces(&mut self, pos: &pos::Position) {
-        let pos = pos.from_ply(self.draw_ply);
+    /// syncs to the ply being shown
+    pub fn sync_pieces(&mut self, history: &rules::History) {
+        self.sync_board(&history.boards[self.draw_ply]);
+    }
 
+    pub fn sync_board(&mut self, board: &rules::Board) {
         self.pieces.clear();
 
         for sq in 0..64 {
-            if pos.piece_on(sq) == piece::NONE {
-                continue;
+            if let Some(piece) = board.squares[sq] {
+                let (f, r) = make_tuple(sq);
+                let xy = self.square_pos(f, r);
+
+                self.pieces.push(VisualPiece {
+                    current_square: sq,
+                    current_pos: xy,
+                    desired_pos: xy,
+                    texture_idx: piece,
+                    movement_duration: time::Duration::ZERO,
+                });
             }
+        }
+    }
 
-            let vec2 = {
-                let (f, r) = pos::make_tuple(sq);
-                let vec2 = vec2(
-                    (f - 7 * self.is_flipped as isize).abs() as f32 * self.square_size,
-                    (r - 7 * !self.is_flipped as isize).abs() as f32 * self.square_size,
-                );
+    /// slides the pieces `mov` moves, `board` is the one before it
+    pub fn make_move(&mut self, board: &rules::Board, mov: rules::Move) {
+        let mut after = board.clone();
+        after.make_move(mov);
+
+        // castling moves the rook too, and a drop doesn't move anything that's on the board
+        let mut moving = mov
+            .source()
+            .map(|from| (from, mov.target()))
+            .into_iter()
+            .collect::<Vec<(rules::Square, rules::Square)>>();
+        if let rules::Move::Castle { king, rook } = mov {
+            moving.push((rook, rules::castled_squares(king, rook).1));
+        }
 
-                self.pos + vec2
-            };
+        // anything else that isn't on the board afterwards was taken, en passant included
+        self.pieces.retain(|p| {
+            moving.iter().any(|(from, _)| *from == p.current_square)
+                || after.squares[p.current_square] == Some(p.texture_idx)
+        });
+
+        // every piece is looked at once, so a king landing on its rook's square isn't moved twice
+        for piece in &mut self.pieces {
+            if let Some((_, to)) = moving
+                .iter()
+                .find(|(from, _)| *from == piece.current_square)
+            {
+                piece.current_square = *to;
+                piece.texture_idx = after.squares[*to].unwrap_or(piece.texture_idx);
+            }
+        }
+
+        if let rules::Move::Drop { to, .. } = mov {
+            let (f, r) = make_tuple(to);
+            let xy = self.square_pos(f, r);
 
             self.pieces.push(VisualPiece {
-                current_square: sq,
-                current_pos: vec2,
-                desired_pos: vec2,
-                texture_idx: bb::p_to_idx(pos.piece_on(sq)),
+                current_square: to,
+                current_pos: xy,
+                desired_pos: xy,
+                texture_idx: after.squares[to].unwrap_or_default(),
                 movement_duration: time::Duration::ZERO,
             });
         }
     }
 
-    pub fn make_move(&mut self, mov: moves::Move, side_to_move: chess_color::Color) {
-        match mov.type_of() {
-            moves::MoveType::Capture(_) | moves::MoveType::PromoCapture(_, _) => {
-                let idx = self
-                    .pieces
-                    .iter()
-                    .position(|p| p.current_square == mov.to_sq());
-
-                if let Some(idx) = idx {
-                    self.pieces.remove(idx);
-                }
-            }
-            moves::MoveType::KingSideCastle => {
-                for piece in &mut self.pieces {
-                    if piece.current_square == pos::WK_ROOK_SQ && side_to_move == chess_color::BLACK
-                        || piece.current_square == pos::BK_ROOK_SQ
-                            && side_to_move == chess_color::WHITE
-                    {
-                        piece.current_square -= 2;
-                    }
-                }
-            }
+    /// slides the pieces `mov` moved back, whatever it took comes back once they've arrived and
+    /// the pieces are synced
+    pub fn unmake_move(&mut self, mov: rules::Move) {
+        let mut moving = mov
+            .source()
+            .map(|from| (mov.target(), from))
+            .into_iter()
+            .collect::<Vec<(rules::Square, rules::Square)>>();
+        if let rules::Move::Castle { king, rook } = mov {
+            moving.push((rules::castled_squares(king, rook).1, rook));
+        }
 
-            moves::MoveType::QueenSideCastle => {
-                for piece in &mut self.pieces {
-                    if piece.current_square == pos::WQ_ROOK_SQ && side_to_move == chess_color::BLACK
-                        || piece.current_square == pos::BQ_ROOK_SQ
-                            && side_to_move == chess_color::WHITE
-                    {
-                        piece.current_square += 3;
-                    }
-                }
-            }
-            _ => {}
+        // a dropped piece goes straight back into the pocket
+        if let rules::Move::Drop { to, .. } = mov {
+            self.pieces.retain(|p| p.current_square != to);
         }
+
         for piece in &mut self.pieces {
-            // piece.movement_duration = time::Duration::ZERO;
-            if piece.current_square == mov.from_sq() {
-                piece.current_square = mov.to_sq();
+            if let Some((_, to)) = moving
+                .iter()
+                .find(|(from, _)| *from == piece.current_square)
+            {
+                piece.current_square = *to;
             }
         }
     }
@@ -213,31 +334,77 @@ impl VisualBoard {
         );
     }
 
-    fn highlight_move_squares(&self, mov: Option<moves::Move>) {
-        if let Some(mov) = mov {
-            let ((ff, fr), (tf, tr)) =
-                (pos::make_tuple(mov.from_sq()), pos::make_tuple(mov.to_sq()));
-            self.highlight(ff, fr, YELLOW.with_alpha(0.4));
-            self.highlight(tf, tr, YELLOW.with_alpha(0.4));
+    fn highlight_move_squares(&self, mov: rules::Move) {
+        for sq in mov.source().into_iter().chain([mov.target()]) {
+            let (f, r) = make_tuple(sq);
+            self.highlight(f, r, YELLOW.with_alpha(0.4));
         }
     }
 
-    fn highlight_legal_moves(&self, legal_moves: Vec<moves::Move>) {
-        if self.selected_piece != piece::NONE {
-            let f_sq = self.mouse_input_sqs.down_left.unwrap();
-            let squares = legal_moves
-                .iter()
-                .filter(|m| m.from_sq() == f_sq)
-                .map(|m| m.to_sq())
-                .collect::<Vec<pos::Square>>();
+    /// dots on the empty squares the selected piece can go to, rings around the pieces it can
+    /// take, a castling king's destination is its own rook in chess960 and the square it lands on
+    /// otherwise. a piece held from a Crazyhouse pocket gets dots wherever it can be dropped
+    fn highlight_legal_moves(&self, board: &rules::Board) {
+        if self.selected_piece.is_some() {
+            let f_sq = self.mouse_input_sqs.down_left;
+            if let Some(f_sq) = f_sq {
+                let (ff, fr) = make_tuple(f_sq);
+                self.highlight(ff, fr, YELLOW.with_alpha(0.4));
+            }
+
+            let squares = board
+                .legal_moves()
+                .into_iter()
+                .filter(|m| match *m {
+                    rules::Move::Drop { kind, .. } => self.mouse_input_sqs.held_drop == Some(kind),
+                    _ => f_sq.is_some() && m.source() == f_sq,
+                })
+                .map(|m| match m {
+                    rules::Move::Castle { rook, .. } if board.chess960 => rook,
+                    _ => m.target(),
+                })
+                .collect::<HashSet<rules::Square>>();
 
             for square in squares {
-                let (tf, tr) = pos::make_tuple(square);
-                self.highlight(tf, tr, RED.with_alpha(0.4));
+                let (tf, tr) = make_tuple(square);
+                let center =
+                    self.square_pos(tf, tr) + vec2(self.square_size, self.square_size) / 2.0;
+
+                if board.squares[square].is_none() {
+                    draw_circle(
+                        center.x,
+                        center.y,
+                        self.square_size * 0.15,
+                        LEGAL_MOVE_COLOR,
+                    );
+                } else {
+                    draw_circle_lines(
+                        center.x,
+                        center.y,
+                        self.square_size * 0.45,
+                        self.square_size * 0.08,
+                        LEGAL_MOVE_COLOR,
+                    );
+                }
             }
         }
     }
 
+    fn highlight_premoves(&self) {
+        for (from, to, _) in &self.premoves {
+            let ((ff, fr), (tf, tr)) = (make_tuple(*from), make_tuple(*to));
+            self.highlight(ff, fr, SKYBLUE.with_alpha(0.5));
+            self.highlight(tf, tr, SKYBLUE.with_alpha(0.5));
+        }
+
+        if self.selected_piece.is_none()
+            && let Some(sq) = self.mouse_input_sqs.down_left
+        {
+            let (f, r) = make_tuple(sq);
+            self.highlight(f, r, SKYBLUE.with_alpha(0.3));
+        }
+    }
+
     fn highlight_selected_squares(&self) {
         for sq in &self.selected_squares {
             self.highlight(sq.0, sq.1, PURPLE.with_alpha(0.6));
@@ -245,71 +412,79 @@ impl VisualBoard {
     }
 
     fn draw_arrows(&self) {
+        for (arrow, style) in &self.engine_arrows {
+            self.draw_arrow(*arrow, *style);
+        }
+
         for arrow in &self.arrows {
-            let arrow_x1 = self.pos.x
-                + (arrow.0 - self.is_flipped as isize * 7).abs() as f32 * self.square_size
-                + self.square_size / 2.0;
-            let arrow_y1 = self.pos.y
-                + (arrow.1 - !self.is_flipped as isize * 7).abs() as f32 * self.square_size
-                + self.square_size / 2.0;
-            let arrow_x2 = self.pos.x
-                + (arrow.2 - self.is_flipped as isize * 7).abs() as f32 * self.square_size
-                + self.square_size / 2.0;
-            let arrow_y2 = self.pos.y
-                + (arrow.3 - !self.is_flipped as isize * 7).abs() as f32 * self.square_size
-                + self.square_size / 2.0;
-
-            let thickness = self.square_size / 3.0;
-
-            draw_line(
-                arrow_x1,
-                arrow_y1,
-                arrow_x2,
-                arrow_y2,
-                thickness,
-                ORANGE.with_alpha(0.7),
-            );
+            self.draw_arrow(*arrow, USER_ARROW);
+        }
+    }
 
-            let angle = {
-                let m = (arrow_y2 - arrow_y1) / (arrow_x2 - arrow_x1);
-                m.atan()
-            };
+    fn draw_arrow(&self, arrow: Arrow, style: ArrowStyle) {
+        let arrow_x1 = self.pos.x
+            + (arrow.0 - self.is_flipped as isize * 7).abs() as f32 * self.square_size
+            + self.square_size / 2.0;
+        let arrow_y1 = self.pos.y
+            + (arrow.1 - !self.is_flipped as isize * 7).abs() as f32 * self.square_size
+            + self.square_size / 2.0;
+        let arrow_x2 = self.pos.x
+            + (arrow.2 - self.is_flipped as isize * 7).abs() as f32 * self.square_size
+            + self.square_size / 2.0;
+        let arrow_y2 = self.pos.y
+            + (arrow.3 - !self.is_flipped as isize * 7).abs() as f32 * self.square_size
+            + self.square_size / 2.0;
+
+        let thickness = self.square_size * style.thickness;
+
+        draw_line(
+            arrow_x1,
+            arrow_y1,
+            arrow_x2,
+            arrow_y2,
+            thickness,
+            style.color,
+        );
 
-            let point1 = {
-                let x1 = arrow_x2;
-                let y1 = arrow_y2 + thickness;
+        let angle = {
+            let m = (arrow_y2 - arrow_y1) / (arrow_x2 - arrow_x1);
+            m.atan()
+        };
 
-                let x2 = (x1 - arrow_x2) * angle.cos() - (y1 - arrow_y2) * angle.sin();
-                let y2 = (x1 - arrow_x2) * angle.sin() + (y1 - arrow_y2) * angle.cos();
+        let point1 = {
+            let x1 = arrow_x2;
+            let y1 = arrow_y2 + thickness;
 
-                vec2(x2 + arrow_x2, y2 + arrow_y2)
-            };
-            let point2 = {
-                let x1 = arrow_x2;
-                let y1 = arrow_y2 - thickness;
+            let x2 = (x1 - arrow_x2) * angle.cos() - (y1 - arrow_y2) * angle.sin();
+            let y2 = (x1 - arrow_x2) * angle.sin() + (y1 - arrow_y2) * angle.cos();
 
-                let x2 = (x1 - arrow_x2) * angle.cos() - (y1 - arrow_y2) * angle.sin();
-                let y2 = (x1 - arrow_x2) * angle.sin() + (y1 - arrow_y2) * angle.cos();
+            vec2(x2 + arrow_x2, y2 + arrow_y2)
+        };
+        let point2 = {
+            let x1 = arrow_x2;
+            let y1 = arrow_y2 - thickness;
 
-                vec2(x2 + arrow_x2, y2 + arrow_y2)
-            };
-            let point3 = {
-                let x1 = arrow_x2
-                    + if arrow_x2 < arrow_x1 {
-                        -thickness
-                    } else {
-                        thickness
-                    };
-                let y1 = arrow_y2;
+            let x2 = (x1 - arrow_x2) * angle.cos() - (y1 - arrow_y2) * angle.sin();
+            let y2 = (x1 - arrow_x2) * angle.sin() + (y1 - arrow_y2) * angle.cos();
+
+            vec2(x2 + arrow_x2, y2 + arrow_y2)
+        };
+        let point3 = {
+            let x1 = arrow_x2
+                + if arrow_x2 < arrow_x1 {
+                    -thickness
+                } else {
+                    thickness
+                };
+            let y1 = arrow_y2;
 
-                let x2 = (x1 - arrow_x2) * angle.cos() - (y1 - arrow_y2) * angle.sin();
-                let y2 = (x1 - arrow_x2) * angle.sin() + (y1 - arrow_y2) * angle.cos();
+            let x2 = (x1 - arrow_x2) * angle.cos() - (y1 - arrow_y2) * angle.sin();
+            let y2 = (x1 - arrow_x2) * angle.sin() + (y1 - arrow_y2) * angle.cos();
 
-                vec2(x2 + arrow_x2, y2 + arrow_y2)
-            };
+            vec2(x2 + arrow_x2, y2 + arrow_y2)
+        };
 
-            draw_triangle(point1, point2, point3, ORANGE.with_alpha(0.7));
-        }
+        draw_triangle(point1, point2, point3, style.color);
     }
 
     fn draw_board_squares(&mut self, xy: Vec2, square_size: f32) {
@@ -333,7 +508,7 @@ impl VisualBoard {
         if (self.square_size, self.pos) != (square_size, xy) {
             for piece in &mut self.pieces {
                 let new_pos = {
-                    let (f, r) = pos::make_tuple(piece.current_square);
+                    let (f, r) = make_tuple(piece.current_square);
                     let vec2 = vec2(f as f32 * square_size, (r - 7).abs() as f32 * square_size);
                     xy + vec2
                 };
@@ -399,7 +574,8 @@ impl VisualBoard {
         }
     }
 
-    fn draw_pieces(&mut self, pos: &pos::Position) {
+    /// `board` is what the pieces are synced to once one of them has finished sliding
+    fn draw_pieces(&mut self, board: &rules::Board) {
         let params = DrawTextureParams {
             dest_size: Some(vec2(self.square_size, self.square_size)),
             source: None,
@@ -413,7 +589,7 @@ impl VisualBoard {
 
         for piece in &mut self.pieces {
             piece.desired_pos = {
-                let (f, r) = pos::make_tuple(piece.current_square);
+                let (f, r) = make_tuple(piece.current_square);
                 let vec2 = vec2(
                     (f - 7 * self.is_flipped as isize).abs() as f32 * self.square_size,
                     (r - 7 * !self.is_flipped as isize).abs() as f32 * self.square_size,
@@ -451,55 +627,166 @@ impl VisualBoard {
                 piece.current_pos
             };
 
-            draw_texture_ex(
-                &self.piece_textures[self.selected_piece_theme][piece.texture_idx],
-                piece.current_pos.x,
-                piece.current_pos.y,
-                WHITE,
-                params.clone(),
-            );
+            if !self.piece_display.shows(piece.texture_idx) {
+                continue;
+            }
+
+            if self.piece_display == PieceDisplay::Discs {
+                draw_circle(
+                    piece.current_pos.x + self.square_size / 2.0,
+                    piece.current_pos.y + self.square_size / 2.0,
+                    self.square_size * 0.35,
+                    GRAY,
+                );
+            } else {
+                draw_texture_ex(
+                    &self.piece_textures[self.selected_piece_theme][piece.texture_idx],
+                    piece.current_pos.x,
+                    piece.current_pos.y,
+                    WHITE,
+                    params.clone(),
+                );
+            }
         }
 
         if sync {
-            self.sync_pieces(pos);
+            self.sync_board(board);
         }
     }
 
-    pub fn draw_board(
-        &mut self,
-        position: Option<&mut pos::Position>,
-        lc_data: &libchess::InitData,
-    ) -> time::Duration {
-        let draw_time = time::Instant::now();
+    pub fn draw_piece_texture(&self, texture_idx: usize, xy: Vec2, size: f32) {
+        draw_texture_ex(
+            &self.piece_textures[self.selected_piece_theme][texture_idx],
+            xy.x,
+            xy.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(size, size)),
+                ..Default::default()
+            },
+        );
+    }
 
+    /// the screen area of every piece that can be in a Crazyhouse pocket, by texture index, in a
+    /// column left of the board with each side's pocket at its own end
+    pub fn pocket_slots(&self) -> Vec<(usize, Rect)> {
+        let size = self.eval_bar_width;
+
+        (0..12)
+            .filter(|piece| piece % 6 != rules::KING)
+            .map(|piece| {
+                let at_bottom = (piece / 6 == 0) != self.is_flipped;
+                let row = if at_bottom { 9 - piece % 6 } else { piece % 6 };
+
+                (
+                    piece,
+                    Rect::new(
+                        self.pos.x - size - 5.0,
+                        self.pos.y + row as f32 * size,
+                        size,
+                        size,
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// empty slots are left grey, the rest show the piece and how many of it there are
+    fn draw_pockets(&self, board: &rules::Board) {
+        for (piece, rect) in self.pocket_slots() {
+            let count = board.pockets[piece / 6][piece % 6];
+
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, GRAY);
+
+            if self.mouse_input_sqs.held_drop == Some(piece % 6) && piece / 6 == board.side() {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, YELLOW.with_alpha(0.4));
+            }
+
+            if count > 0 && self.piece_display.shows(piece) {
+                self.draw_piece_texture(piece, rect.point(), rect.w);
+                draw_text(
+                    &count.to_string(),
+                    rect.x + rect.w * 0.7,
+                    rect.y + rect.h * 0.95,
+                    rect.h * 0.4,
+                    WHITE,
+                );
+            }
+        }
+    }
+
+    /// draws only the squares and their coordinates, `pockets` makes room for Crazyhouse
+    /// pockets on the left
+    pub fn draw_empty_board(&mut self, pockets: bool) {
         // self.draw_board_outline(LIGHTGRAY);
 
+        let square_size = if screen_height() < screen_width() {
+            screen_height()
+        } else {
+            screen_width()
+        } / 8.5;
+
+        // both pockets have five slots and fill the height of the board between them
+        self.eval_bar_width = if pockets { square_size * 0.8 } else { 0.0 };
+
         self.draw_board_squares(
             vec2(
                 self.eval_bar_width + 10.0,
                 screen_height() / 2.0 - self.board_width() / 2.0,
             ),
-            if screen_height() < screen_width() {
-                screen_height()
-            } else {
-                screen_width()
-            } / 8.5,
+            square_size,
         );
 
         self.draw_square_coords();
+    }
 
-        if let Some(position) = position {
-            if position.moves_opt().len() > 0 {
-                self.highlight_move_squares(position.moves_opt()[self.draw_ply])
+    /// draws a position from the board editor, which may not be a legal one
+    pub fn draw_setup(&mut self, setup: &board_editor::SetupPosition) {
+        self.draw_empty_board(false);
+
+        for r in 0..8 {
+            for f in 0..8 {
+                if let Some(piece) = setup.piece_on(f, r) {
+                    self.draw_piece_texture(piece, self.square_pos(f, r), self.square_size);
+                }
             }
+        }
+    }
 
-            self.highlight_selected_squares();
-            self.highlight_legal_moves(moves::gen_legal(position, &lc_data));
+    pub fn draw_board(&mut self, history: Option<&rules::History>) -> time::Duration {
+        let draw_time = time::Instant::now();
 
-            self.draw_pieces(position);
+        self.draw_empty_board(history.is_some_and(|h| h.current().has_pockets()));
+
+        if let Some(history) = history {
+            if self.draw_ply > 0 {
+                self.highlight_move_squares(history.moves[self.draw_ply - 1]);
+            }
+
+            self.highlight_selected_squares();
+            self.highlight_premoves();
+            self.highlight_legal_moves(history.current());
+
+            let board = &history.boards[self.draw_ply];
+            self.draw_pieces(board);
+
+            if board.has_pockets() {
+                self.draw_pockets(board);
+
+                // a piece being dragged out of the pocket follows the mouse
+                if let Some(kind) = self.mouse_input_sqs.held_drop
+                    && is_mouse_button_down(MouseButton::Left)
+                {
+                    self.draw_piece_texture(
+                        kind + board.side() * 6,
+                        Vec2::from(mouse_position()) - vec2(1.0, 1.0) * self.square_size / 2.0,
+                        self.square_size,
+                    );
+                }
+            }
         } else {
             self.draw_ply = 0;
-            self.draw_pieces(&pos::Position::from_fen(pos::START_FEN, &lc_data.zb));
+            self.draw_pieces(&rules::Board::start());
         }
 
         self.draw_arrows();
@@ -507,3 +794,11 @@ impl VisualBoard {
         draw_time.elapsed()
     }
 }
+
+/// the file and rank of a square, the way the board is drawn
+fn make_tuple(sq: rules::Square) -> (pos::File, pos::Rank) {
+    (
+        rules::file_of(sq) as pos::File,
+        rules::rank_of(sq) as pos::Rank,
+    )
+}